@@ -0,0 +1,229 @@
+use std::fmt::Display;
+
+pub type Instructions = Vec<u8>;
+
+// Every instruction is a single opcode byte followed by its operands in big endian
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Opcode {
+    // Push constants[index]
+    Constant,
+    Pop,
+
+    True,
+    False,
+    Null,
+
+    // Binary operators, pop the right then the left operand
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Equal,
+    NotEqual,
+    LessThan,
+    GreaterThan,
+    LessEqual,
+    GreaterEqual,
+
+    // Unary operators
+    Minus,
+    Bang,
+
+    // Jump to an absolute offset in the current function
+    Jump,
+    JumpNotTruthy,
+
+    // Replace the value on top of the stack with an iterator over it
+    Iter,
+    // Push the next element of the iterator on top of the stack, or jump to the
+    // operand once it is exhausted
+    IterNext,
+
+    GetGlobal,
+    SetGlobal,
+    // Like SetGlobal, but fails when the global has not been defined yet
+    AssignGlobal,
+    GetLocal,
+    SetLocal,
+    // Reads a local of an enclosing function, operands are the depth and the index
+    GetFree,
+    SetFree,
+    // Push the value of lookups[index], a name that was not defined yet where it was used
+    GetName,
+    // Give the body of a for loop new locals for every iteration, so that closures created
+    // in it keep the values of that iteration. The operand is the number of locals
+    EnterScope,
+    LeaveScope,
+
+    // Build a collection from the top elements (or key value pairs) on the stack
+    Array,
+    Hash,
+    // Join the top values on the stack into a string, like an interpolated string does
+    Concat,
+    Index,
+    // Look up the binding named constants[index] in the module on top of the stack
+    Member,
+
+    // The operand is the number of arguments on top of the function
+    Call,
+    ReturnValue,
+    // Wrap constants[index] (a compiled function) into a closure over the current locals
+    Closure,
+    // Push modules[index], running the module first if it has not been imported yet
+    Import,
+}
+
+const OPCODES: [Opcode; 42] = [
+    Opcode::Constant,
+    Opcode::Pop,
+    Opcode::True,
+    Opcode::False,
+    Opcode::Null,
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::Mod,
+    Opcode::Pow,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::LessThan,
+    Opcode::GreaterThan,
+    Opcode::LessEqual,
+    Opcode::GreaterEqual,
+    Opcode::Minus,
+    Opcode::Bang,
+    Opcode::Jump,
+    Opcode::JumpNotTruthy,
+    Opcode::Iter,
+    Opcode::IterNext,
+    Opcode::GetGlobal,
+    Opcode::SetGlobal,
+    Opcode::AssignGlobal,
+    Opcode::GetLocal,
+    Opcode::SetLocal,
+    Opcode::GetFree,
+    Opcode::SetFree,
+    Opcode::GetName,
+    Opcode::EnterScope,
+    Opcode::LeaveScope,
+    Opcode::Array,
+    Opcode::Hash,
+    Opcode::Concat,
+    Opcode::Index,
+    Opcode::Member,
+    Opcode::Call,
+    Opcode::ReturnValue,
+    Opcode::Closure,
+    Opcode::Import,
+];
+
+impl Opcode {
+    // The width in bytes of each operand
+    pub fn operand_widths(self) -> &'static [usize] {
+        match self {
+            Opcode::Constant
+            | Opcode::Jump
+            | Opcode::JumpNotTruthy
+            | Opcode::IterNext
+            | Opcode::GetGlobal
+            | Opcode::SetGlobal
+            | Opcode::AssignGlobal
+            | Opcode::GetLocal
+            | Opcode::SetLocal
+            | Opcode::GetName
+            | Opcode::EnterScope
+            | Opcode::Array
+            | Opcode::Hash
+            | Opcode::Concat
+            | Opcode::Member
+            | Opcode::Closure
+            | Opcode::Import => &[2],
+            Opcode::GetFree | Opcode::SetFree => &[1, 2],
+            Opcode::Call => &[1],
+            _ => &[],
+        }
+    }
+}
+
+impl TryFrom<u8> for Opcode {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        OPCODES.get(value as usize).copied().ok_or(value)
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Op{self:?}")
+    }
+}
+
+pub fn make(op: Opcode, operands: &[usize]) -> Instructions {
+    let mut instruction = vec![op as u8];
+
+    for (operand, width) in operands.iter().zip(op.operand_widths()) {
+        match width {
+            1 => instruction.push(*operand as u8),
+            2 => instruction.extend_from_slice(&(*operand as u16).to_be_bytes()),
+            _ => unreachable!("operands are either 1 or 2 bytes wide"),
+        }
+    }
+
+    instruction
+}
+
+pub fn read_u8(instructions: &[u8], offset: usize) -> usize {
+    instructions[offset] as usize
+}
+
+pub fn read_u16(instructions: &[u8], offset: usize) -> usize {
+    u16::from_be_bytes([instructions[offset], instructions[offset + 1]]) as usize
+}
+
+// Decodes the operands of `op` starting at `offset`, returning them and the number of bytes read
+pub fn read_operands(op: Opcode, instructions: &[u8], offset: usize) -> (Vec<usize>, usize) {
+    let mut operands = Vec::new();
+    let mut read = 0;
+
+    for width in op.operand_widths() {
+        match width {
+            1 => operands.push(read_u8(instructions, offset + read)),
+            2 => operands.push(read_u16(instructions, offset + read)),
+            _ => unreachable!("operands are either 1 or 2 bytes wide"),
+        }
+        read += width;
+    }
+
+    (operands, read)
+}
+
+// Human readable listing of the instructions, one per line prefixed with its offset
+pub fn disassemble(instructions: &[u8]) -> String {
+    let mut output = Vec::new();
+    let mut offset = 0;
+
+    while offset < instructions.len() {
+        let Ok(op) = Opcode::try_from(instructions[offset]) else {
+            output.push(format!("{offset:04} ERROR: unknown opcode {}", instructions[offset]));
+            offset += 1;
+            continue;
+        };
+
+        let (operands, read) = read_operands(op, instructions, offset + 1);
+
+        let mut line = format!("{offset:04} {op}");
+        for operand in operands {
+            line.push_str(&format!(" {operand}"));
+        }
+        output.push(line);
+
+        offset += 1 + read;
+    }
+
+    output.join("\n")
+}
@@ -0,0 +1,998 @@
+use std::{cell::RefCell, collections::HashMap, fmt::Display, path::PathBuf, rc::Rc};
+
+use crate::{
+    error::{EvaluatorError, EvaluatorErrorKind},
+    eval::{
+        funcs::NativeFunction,
+        module::{self, error_in_module, Modules},
+        value::{HashKey, Value},
+    },
+    parser::{
+        ast::{Expression, ExpressionKind, Statement, StatementKind},
+        program::Program,
+    },
+    token::{Span, Token},
+};
+
+use code::{make, Instructions, Opcode};
+use symbol_table::{Symbol, SymbolScope, SymbolTable};
+
+pub mod code;
+pub mod symbol_table;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Default)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    // The span of the expression each instruction was compiled from, keyed by the offset of
+    // the first instruction with that span. Used to locate runtime errors
+    pub spans: Vec<(usize, Span)>,
+    pub num_locals: usize,
+    pub params: Vec<String>,
+    // The source of the body, only used to display the function
+    pub body: String,
+    // The module the function was written in, whose globals it uses. `None` for the main
+    // program
+    pub module: Option<usize>,
+}
+
+impl CompiledFunction {
+    pub fn span_at(&self, offset: usize) -> Option<Span> {
+        let i = self.spans.partition_point(|(start, _)| *start <= offset);
+        i.checked_sub(1).map(|i| self.spans[i].1)
+    }
+}
+
+impl Display for CompiledFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "fn({}) {{\n{}\n}}", self.params.join(", "), self.body)
+    }
+}
+
+pub struct Bytecode {
+    pub main: Rc<CompiledFunction>,
+    pub pool: Rc<RefCell<Pool>>,
+}
+
+// Everything the instructions refer to by index
+//
+// The pool only ever grows, so the compiler shares it with every `Bytecode` it produces
+// instead of copying it for each program
+#[derive(Debug, Default)]
+pub struct Pool {
+    pub constants: Vec<Value>,
+    pub functions: Vec<Rc<CompiledFunction>>,
+    // Names of the globals by index, used for "identifier not found" errors
+    pub global_names: Vec<String>,
+    pub modules: Vec<Rc<CompiledModule>>,
+    pub lookups: Vec<Lookup>,
+    // Host functions, found by lookups when the program has no binding with their name
+    pub natives: HashMap<String, Rc<NativeFunction>>,
+}
+
+// A name that was not defined yet in the scope it was used in
+//
+// The evaluator looks names up while running, so a binding that is defined later on in an
+// enclosing scope (or by the next program in the REPL) is still found. The vm does the same
+// by checking the slots the name has in the enclosing scopes, innermost first, and then the
+// host functions and builtins
+#[derive(Debug, Default)]
+pub struct Lookup {
+    pub name: String,
+    // Relative to the scope the name was used in. Filled in as the enclosing scopes are
+    // finished, since only then is everything they define known
+    pub slots: Vec<Symbol>,
+}
+
+// An imported file, which has globals of its own and runs the first time it is imported
+#[derive(Debug, Default)]
+pub struct CompiledModule {
+    // The path as it was written in the import
+    pub path: String,
+    pub main: Rc<CompiledFunction>,
+    pub global_names: Vec<String>,
+    // The global index of every top-level binding, which become the bindings of the module
+    pub bindings: Vec<(String, usize)>,
+}
+
+#[derive(Default)]
+struct CompilationScope {
+    instructions: Instructions,
+    spans: Vec<(usize, Span)>,
+    // Number of operands currently waiting on the stack, which have to be dropped when
+    // a `break` or `continue` jumps out of the middle of an expression
+    depth: usize,
+    // Number of for loop bodies currently open, whose scopes have to be left when a `break`
+    // or `continue` jumps out of them
+    blocks: usize,
+    loops: Vec<Loop>,
+}
+
+// A loop being compiled in the current function
+struct Loop {
+    // Offset of the condition, where `continue` jumps to
+    start: usize,
+    // Operands on the stack and loop bodies open when the loop was entered
+    depth: usize,
+    blocks: usize,
+    // Jumps to be patched to the end of the loop
+    breaks: Vec<usize>,
+}
+
+// Lowers a `Program` into bytecode for the `Vm`
+//
+// The constants, functions and globals are kept between calls to `compile` so that
+// programs compiled one after another (like in the REPL) can use each others bindings
+#[derive(Default)]
+pub struct Compiler {
+    pool: Rc<RefCell<Pool>>,
+    // Constants that can be compared are only added to the pool once
+    constant_indices: HashMap<HashKey, usize>,
+    symbol_table: SymbolTable,
+    // For every symbol table that is open, the lookups used in it (or in the tables it
+    // encloses) with how many scopes out from where they were used it is
+    unresolved: Vec<Vec<(usize, usize)>>,
+    // The lookups used at the top level of the main program, which always find the same
+    // global and are shared by every program compiled
+    global_lookups: HashMap<String, usize>,
+    scopes: Vec<CompilationScope>,
+    modules: Modules<usize>,
+    // The module being compiled, `None` for the main program
+    module: Option<usize>,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Compiler::default()
+    }
+
+    // Sets the directory that imports in the main program are relative to
+    pub fn set_import_dir(&mut self, dir: impl Into<PathBuf>) {
+        self.modules.dir = dir.into();
+    }
+
+    // Makes a host function callable by name, with the same precedence as in the evaluator
+    pub fn register_function(&mut self, native: Rc<NativeFunction>) {
+        let natives = &mut self.pool.borrow_mut().natives;
+        natives.insert(native.name.clone(), native);
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<Bytecode, EvaluatorError> {
+        self.scopes = vec![CompilationScope::default()];
+        self.unresolved = vec![Vec::new()];
+
+        for stmt in &program.statments {
+            self.compile_statement(stmt)?;
+        }
+
+        self.resolve_globals();
+
+        let scope = self.scopes.pop().unwrap_or_default();
+
+        // Only the globals defined by this program are new
+        let names = self.symbol_table.names();
+        let global_names = &mut self.pool.borrow_mut().global_names;
+        global_names.extend_from_slice(&names[global_names.len()..]);
+
+        Ok(Bytecode {
+            main: Rc::new(CompiledFunction {
+                instructions: scope.instructions,
+                spans: scope.spans,
+                ..Default::default()
+            }),
+            pool: Rc::clone(&self.pool),
+        })
+    }
+
+    // Statements leave the stack as they found it
+    fn compile_statement(&mut self, stmt: &Statement) -> Result<(), EvaluatorError> {
+        match &stmt.kind {
+            StatementKind::LetStatement { ident, value } => {
+                let name = ident.to_string();
+
+                // Functions can refer to themselves so the name has to be defined first,
+                // any other value may still refer to an outer binding with the same name
+                let symbol = match value.kind {
+                    ExpressionKind::FunctionExpression { .. } => {
+                        let symbol = self.symbol_table.define(&name);
+                        self.compile_expression(value)?;
+                        symbol
+                    }
+                    _ => {
+                        self.compile_expression(value)?;
+                        self.symbol_table.define(&name)
+                    }
+                };
+
+                self.set_symbol(symbol, stmt.span)?;
+            }
+            StatementKind::ReturnStatement(value) => {
+                self.compile_expression(value)?;
+                self.emit(Opcode::ReturnValue, &[], stmt.span)?;
+            }
+            StatementKind::ExpressionStatement(expr) => {
+                self.compile_expression(expr)?;
+                self.emit(Opcode::Pop, &[], stmt.span)?;
+            }
+            StatementKind::BlockStatement(statements) => {
+                for stmt in statements {
+                    self.compile_statement(stmt)?;
+                }
+            }
+            StatementKind::WhileStatement { condition, body } => {
+                self.compile_while_statement(condition, body, stmt.span)?;
+            }
+            StatementKind::ForStatement {
+                ident,
+                iterable,
+                body,
+            } => {
+                self.compile_for_statement(ident, iterable, body, stmt.span)?;
+            }
+            StatementKind::BreakStatement => {
+                self.leave_loop_scopes(stmt.span)?;
+
+                let jump = self.emit_jump(Opcode::Jump, stmt.span)?;
+                self.current_loop().breaks.push(jump);
+            }
+            StatementKind::ContinueStatement => {
+                self.leave_loop_scopes(stmt.span)?;
+
+                let start = self.current_loop().start;
+                self.emit(Opcode::Jump, &[start], stmt.span)?;
+            }
+            StatementKind::ImportStatement { path, ident } => {
+                let index = self
+                    .compile_import(path)
+                    .map_err(|err| err.or_span(stmt.span))?;
+                let index = self.check_limit(index, "modules", stmt.span)?;
+                self.emit(Opcode::Import, &[index], stmt.span)?;
+
+                let symbol = self.symbol_table.define(ident);
+                self.set_symbol(symbol, stmt.span)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Compiles the module the first time it is imported, returning its index
+    fn compile_import(&mut self, path: &str) -> Result<usize, EvaluatorError> {
+        let resolved = self.modules.resolve(path)?;
+        if let Some(index) = self.modules.get(&resolved) {
+            return Ok(index);
+        }
+
+        self.modules.enter(resolved.clone(), path)?;
+        let result = module::parse(&resolved, path)
+            .and_then(|program| self.compile_module(path, &program));
+        self.modules.leave();
+
+        let index = result?;
+        self.modules.insert(resolved, index);
+
+        Ok(index)
+    }
+
+    // The top level of a module is compiled like a program of its own, into a function that
+    // the `Import` instruction calls
+    fn compile_module(&mut self, path: &str, program: &Program) -> Result<usize, EvaluatorError> {
+        // Reserved up front since the modules it imports are compiled first
+        let index = {
+            let modules = &mut self.pool.borrow_mut().modules;
+            modules.push(Rc::default());
+            modules.len() - 1
+        };
+
+        let symbol_table = std::mem::take(&mut self.symbol_table);
+        let unresolved = std::mem::replace(&mut self.unresolved, vec![Vec::new()]);
+        let scopes = std::mem::replace(&mut self.scopes, vec![CompilationScope::default()]);
+        let module = self.module.replace(index);
+
+        let result = program
+            .statments
+            .iter()
+            .try_for_each(|stmt| self.compile_statement(stmt))
+            .and_then(|_| self.emit(Opcode::Null, &[], Span::default()))
+            .and_then(|_| self.emit(Opcode::ReturnValue, &[], Span::default()))
+            .map(|_| self.resolve_globals());
+
+        let scope = self.scopes.pop().unwrap_or_default();
+        let module_table = std::mem::replace(&mut self.symbol_table, symbol_table);
+        self.unresolved = unresolved;
+        self.scopes = scopes;
+        self.module = module;
+
+        result.map_err(|err| error_in_module(path, err))?;
+
+        self.pool.borrow_mut().modules[index] = Rc::new(CompiledModule {
+            path: path.to_string(),
+            main: Rc::new(CompiledFunction {
+                instructions: scope.instructions,
+                spans: scope.spans,
+                module: Some(index),
+                ..Default::default()
+            }),
+            global_names: module_table.names().to_vec(),
+            bindings: module_table.bindings(),
+        });
+
+        Ok(index)
+    }
+
+    fn compile_while_statement(
+        &mut self,
+        condition: &Expression,
+        body: &Statement,
+        span: Span,
+    ) -> Result<(), EvaluatorError> {
+        let start = self.scope().instructions.len();
+
+        self.compile_expression(condition)?;
+        let exit = self.emit_jump(Opcode::JumpNotTruthy, span)?;
+
+        self.push_loop(start, 0);
+
+        let result = self
+            .compile_statement(body)
+            .and_then(|_| self.emit(Opcode::Jump, &[start], span));
+
+        let finished = self.scope().loops.pop().expect("loop was pushed above");
+        result?;
+
+        self.patch_jump(exit);
+        for jump in finished.breaks {
+            self.patch_jump(jump);
+        }
+
+        // Like the evaluator, a loop evaluates to null
+        self.emit(Opcode::Null, &[], span)?;
+        self.emit(Opcode::Pop, &[], span)
+    }
+
+    fn compile_for_statement(
+        &mut self,
+        ident: &Token,
+        iterable: &Expression,
+        body: &Statement,
+        span: Span,
+    ) -> Result<(), EvaluatorError> {
+        self.compile_expression(iterable)?;
+        self.emit(Opcode::Iter, &[], iterable.span)?;
+
+        // The iterator stays on the stack for as long as the loop runs
+        self.scope().depth += 1;
+
+        let start = self.scope().instructions.len();
+        let (exit, finished) = self.compile_for_body(ident, body, start, span)?;
+
+        self.patch_jump(exit);
+        for jump in finished.breaks {
+            self.patch_jump(jump);
+        }
+
+        self.drop_operands(1);
+        self.emit(Opcode::Pop, &[], span)?;
+
+        // Like the evaluator, a loop evaluates to null
+        self.emit(Opcode::Null, &[], span)?;
+        self.emit(Opcode::Pop, &[], span)
+    }
+
+    // Returns the jump taken once the iterator is exhausted and the finished loop
+    //
+    // Like the evaluator creates an environment for every iteration, the body has a scope of
+    // its own that is entered again for every element
+    //
+    //   start: IterNext exit
+    //          EnterScope <locals>
+    //          SetLocal 0
+    //          <body>
+    //          LeaveScope
+    //          Jump start
+    //   exit:
+    fn compile_for_body(
+        &mut self,
+        ident: &Token,
+        body: &Statement,
+        start: usize,
+        span: Span,
+    ) -> Result<(usize, Loop), EvaluatorError> {
+        let exit = self.emit_jump(Opcode::IterNext, span)?;
+        // The number of locals is patched in once the body has been compiled
+        let enter = self.scope().instructions.len();
+        self.emit(Opcode::EnterScope, &[0], span)?;
+
+        self.enter_symbol_table();
+        self.scope().blocks += 1;
+        self.push_loop(start, 1);
+
+        let symbol = self.symbol_table.define(&ident.to_string());
+        let result = self
+            .set_symbol(symbol, span)
+            .and_then(|_| self.compile_statement(body))
+            .and_then(|_| self.emit(Opcode::LeaveScope, &[], span))
+            .and_then(|_| self.emit(Opcode::Jump, &[start], span));
+
+        let finished = self.scope().loops.pop().expect("loop was pushed above");
+        self.scope().blocks -= 1;
+        let num_locals = self.leave_symbol_table();
+
+        result?;
+
+        let num_locals = self.check_limit(num_locals, "local variables", span)?;
+        self.patch_operand(enter, num_locals);
+
+        Ok((exit, finished))
+    }
+
+    // `blocks` is the number of loop bodies the loop opens itself, which `continue` leaves
+    // to go back to the start of the loop
+    fn push_loop(&mut self, start: usize, blocks: usize) {
+        let scope = self.scope();
+        let (depth, blocks) = (scope.depth, scope.blocks - blocks);
+
+        scope.loops.push(Loop {
+            start,
+            depth,
+            blocks,
+            breaks: Vec::new(),
+        });
+    }
+
+    // Pops the operands pushed and leaves the loop bodies entered since the innermost loop
+    // was entered
+    fn leave_loop_scopes(&mut self, span: Span) -> Result<(), EvaluatorError> {
+        let pending = self.scope().depth - self.current_loop().depth;
+        let blocks = self.scope().blocks - self.current_loop().blocks;
+
+        for _ in 0..pending {
+            self.emit(Opcode::Pop, &[], span)?;
+        }
+        for _ in 0..blocks {
+            self.emit(Opcode::LeaveScope, &[], span)?;
+        }
+
+        Ok(())
+    }
+
+    // The parser only allows `break` and `continue` inside of a loop
+    fn current_loop(&mut self) -> &mut Loop {
+        self.scope()
+            .loops
+            .last_mut()
+            .expect("loop control outside of a loop")
+    }
+
+    // Compiles a block so that it leaves the value of its last statement on the stack,
+    // the same value `Evaluator::eval_block` would produce
+    fn compile_block_value(&mut self, block: &Statement) -> Result<(), EvaluatorError> {
+        let statements = match &block.kind {
+            StatementKind::BlockStatement(statements) => statements.as_slice(),
+            _ => std::slice::from_ref(block),
+        };
+
+        let Some((last, rest)) = statements.split_last() else {
+            return self.emit(Opcode::Null, &[], block.span);
+        };
+
+        for stmt in rest {
+            self.compile_statement(stmt)?;
+        }
+
+        match &last.kind {
+            StatementKind::ExpressionStatement(expr) => self.compile_expression(expr),
+            StatementKind::LetStatement { ident, .. } => {
+                self.compile_statement(last)?;
+
+                // A let statement evaluates to the value that was bound
+                let symbol = self.symbol_table.resolve(&ident.to_string());
+                self.get_symbol(symbol.unwrap(), last.span)
+            }
+            StatementKind::ReturnStatement(_) => {
+                self.compile_statement(last)?;
+
+                // Never reached, but keeps the stack balanced for the code that follows
+                self.emit(Opcode::Null, &[], last.span)
+            }
+            StatementKind::ImportStatement { ident, .. } => {
+                self.compile_statement(last)?;
+
+                // Like a let statement, an import evaluates to the module
+                let symbol = self.symbol_table.resolve(ident);
+                self.get_symbol(symbol.unwrap(), last.span)
+            }
+            StatementKind::BlockStatement(_) => self.compile_block_value(last),
+            StatementKind::WhileStatement { .. }
+            | StatementKind::ForStatement { .. }
+            | StatementKind::BreakStatement
+            | StatementKind::ContinueStatement => {
+                self.compile_statement(last)?;
+                self.emit(Opcode::Null, &[], last.span)
+            }
+        }
+    }
+
+    fn compile_expression(&mut self, expr: &Expression) -> Result<(), EvaluatorError> {
+        let span = expr.span;
+
+        match &expr.kind {
+            ExpressionKind::IntExpression(i) => self.emit_constant(Value::Integer(*i), span),
+            ExpressionKind::BigIntExpression(i) => {
+                self.emit_constant(Value::BigInt(i.clone()), span)
+            }
+            ExpressionKind::FloatExpression(f) => self.emit_constant(Value::Float(*f), span),
+            ExpressionKind::StringExpression(s) => {
+                self.emit_constant(Value::String(s.to_string()), span)
+            }
+            ExpressionKind::BooleanExpression(true) => self.emit(Opcode::True, &[], span),
+            ExpressionKind::BooleanExpression(false) => self.emit(Opcode::False, &[], span),
+            ExpressionKind::IdentifierExpression(name) => self.compile_identifier(name, span),
+            ExpressionKind::PrefixExpression { op_token, right } => {
+                self.compile_expression(right)?;
+
+                match op_token {
+                    token![-] => self.emit(Opcode::Minus, &[], span),
+                    _ => self.emit(Opcode::Bang, &[], span),
+                }
+            }
+            ExpressionKind::InfixExpression {
+                left,
+                op_token: op_token @ (token![&&] | token![||]),
+                right,
+            } => self.compile_logical_expression(left, op_token, right, span),
+            ExpressionKind::InfixExpression {
+                left,
+                op_token,
+                right,
+            } => {
+                self.compile_operand(left)?;
+                self.compile_expression(right)?;
+
+                self.drop_operands(1);
+                self.emit(infix_opcode(op_token), &[], span)
+            }
+            ExpressionKind::IfExpression {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                self.compile_expression(condition)?;
+
+                // Jump over the consequence when the condition is not true
+                let jump_not_truthy = self.emit_jump(Opcode::JumpNotTruthy, span)?;
+
+                self.compile_block_value(consequence)?;
+
+                let jump = self.emit_jump(Opcode::Jump, span)?;
+
+                self.patch_jump(jump_not_truthy);
+
+                match alternative {
+                    Some(alternative) => self.compile_block_value(alternative)?,
+                    None => self.emit(Opcode::Null, &[], span)?,
+                }
+
+                self.patch_jump(jump);
+
+                Ok(())
+            }
+            ExpressionKind::FunctionExpression { parameters, body } => {
+                self.compile_function(parameters, body, span)
+            }
+            ExpressionKind::CallExpression {
+                function,
+                arguments,
+            } => {
+                self.compile_operand(function)?;
+
+                for arg in arguments {
+                    self.compile_operand(arg)?;
+                }
+
+                self.drop_operands(1 + arguments.len());
+                self.emit(Opcode::Call, &[arguments.len()], span)
+            }
+            ExpressionKind::ArrayExpression(elements) => {
+                for element in elements {
+                    self.compile_operand(element)?;
+                }
+
+                self.drop_operands(elements.len());
+                let len = self.check_limit(elements.len(), "array elements", span)?;
+                self.emit(Opcode::Array, &[len], span)
+            }
+            ExpressionKind::HashExpression(pairs) => {
+                for (key, value) in pairs {
+                    self.compile_operand(key)?;
+                    self.compile_operand(value)?;
+                }
+
+                self.drop_operands(pairs.len() * 2);
+                let len = self.check_limit(pairs.len(), "hash pairs", span)?;
+                self.emit(Opcode::Hash, &[len], span)
+            }
+            ExpressionKind::TemplateExpression(parts) => {
+                for part in parts {
+                    self.compile_operand(part)?;
+                }
+
+                self.drop_operands(parts.len());
+                let len = self.check_limit(parts.len(), "interpolated string parts", span)?;
+                self.emit(Opcode::Concat, &[len], span)
+            }
+            ExpressionKind::IndexExpression { left, index } => {
+                self.compile_operand(left)?;
+                self.compile_expression(index)?;
+
+                self.drop_operands(1);
+                self.emit(Opcode::Index, &[], span)
+            }
+            ExpressionKind::MemberExpression { left, name } => {
+                self.compile_expression(left)?;
+
+                let index = self.add_constant(Value::String(name.to_string()), span)?;
+                self.emit(Opcode::Member, &[index], span)
+            }
+            ExpressionKind::AssignExpression {
+                name,
+                op_token,
+                value,
+            } => {
+                // An unknown name may still be defined as a global before this code runs,
+                // otherwise assigning to it fails at runtime like in the evaluator
+                let symbol = match self.symbol_table.resolve(name) {
+                    Some(symbol) => symbol,
+                    None => self.symbol_table.define_global(name),
+                };
+
+                match op_token.compound_operator() {
+                    Some(operator) => {
+                        self.get_symbol(symbol, span)?;
+                        self.scope().depth += 1;
+                        self.compile_expression(value)?;
+
+                        self.drop_operands(1);
+                        self.emit(infix_opcode(&operator), &[], span)?;
+                    }
+                    None => self.compile_expression(value)?,
+                }
+
+                self.assign_symbol(symbol, span)?;
+
+                // The assignment evaluates to the new value
+                self.get_symbol(symbol, span)
+            }
+        }
+    }
+
+    // Short-circuits, only evaluating the right operand when it decides the result
+    //
+    //   a && b                          a || b
+    //     <a>                             <a>
+    //     JumpNotTruthy short             JumpNotTruthy right
+    //     <b> Bang Bang                   True
+    //     Jump end                        Jump end
+    //   short: False                    right: <b> Bang Bang
+    //   end:                            end:
+    fn compile_logical_expression(
+        &mut self,
+        left: &Expression,
+        operator: &Token,
+        right: &Expression,
+        span: Span,
+    ) -> Result<(), EvaluatorError> {
+        let is_and = operator == &token![&&];
+
+        self.compile_expression(left)?;
+        let jump_not_truthy = self.emit_jump(Opcode::JumpNotTruthy, span)?;
+
+        if is_and {
+            self.compile_truth(right, span)?;
+        } else {
+            self.emit(Opcode::True, &[], span)?;
+        }
+
+        let jump = self.emit_jump(Opcode::Jump, span)?;
+        self.patch_jump(jump_not_truthy);
+
+        if is_and {
+            self.emit(Opcode::False, &[], span)?;
+        } else {
+            self.compile_truth(right, span)?;
+        }
+
+        self.patch_jump(jump);
+
+        Ok(())
+    }
+
+    // Leaves the truth value of the expression as a boolean, like `!!expr`
+    fn compile_truth(&mut self, expr: &Expression, span: Span) -> Result<(), EvaluatorError> {
+        self.compile_expression(expr)?;
+        self.emit(Opcode::Bang, &[], span)?;
+        self.emit(Opcode::Bang, &[], span)
+    }
+
+    // Compiles an expression whose value stays on the stack while the expressions after it
+    // are compiled, until `drop_operands` is called for the instruction consuming it
+    fn compile_operand(&mut self, expr: &Expression) -> Result<(), EvaluatorError> {
+        self.compile_expression(expr)?;
+        self.scope().depth += 1;
+        Ok(())
+    }
+
+    fn drop_operands(&mut self, count: usize) {
+        self.scope().depth -= count;
+    }
+
+    fn compile_identifier(&mut self, name: &str, span: Span) -> Result<(), EvaluatorError> {
+        // A name defined earlier on in the same scope is always the nearest binding
+        if let Some(symbol) = self.symbol_table.get(name) {
+            return self.get_symbol(symbol, span);
+        }
+
+        // Anything else may still be defined before this code runs
+        let index = self.add_lookup(name, span)?;
+        self.emit(Opcode::GetName, &[index], span)
+    }
+
+    // Returns the lookup of the name in the current scope, adding it if it is the first
+    // time the name is used there
+    fn add_lookup(&mut self, name: &str, span: Span) -> Result<usize, EvaluatorError> {
+        let at_top_level = self.module.is_none() && self.unresolved.len() == 1;
+        if let Some(index) = self.global_lookups.get(name).filter(|_| at_top_level) {
+            return Ok(*index);
+        }
+
+        let lookups = &mut self.pool.borrow_mut().lookups;
+        let index = self.check_limit(lookups.len(), "names", span)?;
+        let unresolved = self.unresolved.last_mut().expect("compiler has no symbol table");
+
+        let used = unresolved
+            .iter()
+            .find(|(lookup, depth)| *depth == 0 && lookups[*lookup].name == name);
+        if let Some((index, _)) = used {
+            return Ok(*index);
+        }
+
+        lookups.push(Lookup {
+            name: name.to_string(),
+            slots: Vec::new(),
+        });
+        unresolved.push((index, 0));
+
+        if at_top_level {
+            self.global_lookups.insert(name.to_string(), index);
+        }
+
+        Ok(index)
+    }
+
+    // Functions and for loop bodies have a symbol table of their own
+    fn enter_symbol_table(&mut self) {
+        self.symbol_table = SymbolTable::new_enclosed(std::mem::take(&mut self.symbol_table));
+        self.unresolved.push(Vec::new());
+    }
+
+    // Goes back to the enclosing symbol table, returning the number of locals the scope of
+    // the table needs
+    //
+    // The lookups used in the scope check the slot of the name in it if it was defined
+    // there, and are passed on to the enclosing scope which may define it too
+    fn leave_symbol_table(&mut self) -> usize {
+        let unresolved = self.unresolved.pop().unwrap_or_default();
+
+        let lookups = &mut self.pool.borrow_mut().lookups;
+        for (lookup, depth) in &unresolved {
+            let lookup = &mut lookups[*lookup];
+
+            if let Some(symbol) = self.symbol_table.get(&lookup.name) {
+                lookup.slots.push(match depth {
+                    0 => symbol,
+                    depth => Symbol {
+                        scope: SymbolScope::Free { depth: *depth },
+                        index: symbol.index,
+                    },
+                });
+            }
+        }
+
+        if let Some(outer) = self.unresolved.last_mut() {
+            outer.extend(unresolved.into_iter().map(|(lookup, depth)| (lookup, depth + 1)));
+        }
+
+        let num_locals = self.symbol_table.num_definitions();
+        self.symbol_table = std::mem::take(&mut self.symbol_table)
+            .into_outer()
+            .unwrap_or_default();
+
+        num_locals
+    }
+
+    // Names that are not defined in any scope around the code using them are globals,
+    // which the program (or the next one) may still define. Until then the slot is only
+    // reserved, so later programs keep looking the name up instead of reading the slot
+    fn resolve_globals(&mut self) {
+        let unresolved = self.unresolved.pop().unwrap_or_default();
+
+        let lookups = &mut self.pool.borrow_mut().lookups;
+        for (lookup, _) in unresolved {
+            let lookup = &mut lookups[lookup];
+
+            let symbol = self.symbol_table.define_global(&lookup.name);
+            lookup.slots.push(symbol);
+        }
+    }
+
+    fn compile_function(
+        &mut self,
+        parameters: &[String],
+        body: &Statement,
+        span: Span,
+    ) -> Result<(), EvaluatorError> {
+        self.scopes.push(CompilationScope::default());
+        self.enter_symbol_table();
+
+        for param in parameters {
+            self.symbol_table.define(param);
+        }
+
+        // The function returns the value of its last statement if it does not return early
+        let result = self
+            .compile_block_value(body)
+            .and_then(|_| self.emit(Opcode::ReturnValue, &[], body.span));
+
+        let scope = self.scopes.pop().unwrap_or_default();
+        let num_locals = self.leave_symbol_table();
+
+        result?;
+
+        self.check_limit(num_locals, "local variables", span)?;
+
+        let func = CompiledFunction {
+            instructions: scope.instructions,
+            spans: scope.spans,
+            num_locals,
+            params: parameters.to_vec(),
+            body: body.to_string(),
+            module: self.module,
+        };
+
+        let index = {
+            let functions = &mut self.pool.borrow_mut().functions;
+            let index = self.check_limit(functions.len(), "functions", span)?;
+            functions.push(Rc::new(func));
+            index
+        };
+
+        self.emit(Opcode::Closure, &[index], span)
+    }
+
+    fn get_symbol(&mut self, symbol: Symbol, span: Span) -> Result<(), EvaluatorError> {
+        match symbol.scope {
+            SymbolScope::Global => self.emit(Opcode::GetGlobal, &[symbol.index], span),
+            SymbolScope::Local => self.emit(Opcode::GetLocal, &[symbol.index], span),
+            SymbolScope::Free { depth } => {
+                self.emit(Opcode::GetFree, &[depth, symbol.index], span)
+            }
+        }
+    }
+
+    fn set_symbol(&mut self, symbol: Symbol, span: Span) -> Result<(), EvaluatorError> {
+        match symbol.scope {
+            SymbolScope::Global => self.emit(Opcode::SetGlobal, &[symbol.index], span),
+            // Let statements always define a symbol in the current scope
+            _ => self.emit(Opcode::SetLocal, &[symbol.index], span),
+        }
+    }
+
+    // Updates an existing binding in the scope that defined it
+    fn assign_symbol(&mut self, symbol: Symbol, span: Span) -> Result<(), EvaluatorError> {
+        match symbol.scope {
+            SymbolScope::Global => self.emit(Opcode::AssignGlobal, &[symbol.index], span),
+            SymbolScope::Local => self.emit(Opcode::SetLocal, &[symbol.index], span),
+            SymbolScope::Free { depth } => {
+                self.emit(Opcode::SetFree, &[depth, symbol.index], span)
+            }
+        }
+    }
+
+    fn emit_constant(&mut self, value: Value, span: Span) -> Result<(), EvaluatorError> {
+        let index = self.add_constant(value, span)?;
+        self.emit(Opcode::Constant, &[index], span)
+    }
+
+    // Returns the index of the constant in the pool, reusing an equal one when it can be
+    // compared
+    fn add_constant(&mut self, value: Value, span: Span) -> Result<usize, EvaluatorError> {
+        let key = value.hash_key();
+        if let Some(index) = key.as_ref().and_then(|key| self.constant_indices.get(key)) {
+            return Ok(*index);
+        }
+
+        let constants = &mut self.pool.borrow_mut().constants;
+        let index = self.check_limit(constants.len(), "constants", span)?;
+        constants.push(value);
+
+        if let Some(key) = key {
+            self.constant_indices.insert(key, index);
+        }
+
+        Ok(index)
+    }
+
+    // Emits a jump with a placeholder target, returning its offset so it can be patched
+    fn emit_jump(&mut self, op: Opcode, span: Span) -> Result<usize, EvaluatorError> {
+        let offset = self.scope().instructions.len();
+        self.emit(op, &[0], span)?;
+        Ok(offset)
+    }
+
+    // Points the jump at `offset` to the next instruction that will be emitted
+    // Targets always fit in the operand as `emit` checks the size of the instructions
+    fn patch_jump(&mut self, offset: usize) {
+        let target = self.scope().instructions.len();
+        self.patch_operand(offset, target);
+    }
+
+    // Replaces the 2 byte operand of the instruction at `offset`
+    fn patch_operand(&mut self, offset: usize, operand: usize) {
+        let instructions = &mut self.scope().instructions;
+        instructions[offset + 1..offset + 3].copy_from_slice(&(operand as u16).to_be_bytes());
+    }
+
+    fn emit(&mut self, op: Opcode, operands: &[usize], span: Span) -> Result<(), EvaluatorError> {
+        // Every offset has to fit in a jump operand
+        let offset = self.scope().instructions.len();
+        self.check_limit(offset, "bytes of instructions", span)?;
+
+        let scope = self.scope();
+
+        // Only record the span when it changes to keep the table small
+        if scope.spans.last().map(|(_, last)| *last) != Some(span) {
+            scope.spans.push((offset, span));
+        }
+
+        scope.instructions.extend(make(op, operands));
+
+        Ok(())
+    }
+
+    fn scope(&mut self) -> &mut CompilationScope {
+        self.scopes.last_mut().expect("compiler has no scope")
+    }
+
+    // Operands are at most 2 bytes wide
+    fn check_limit(&self, value: usize, what: &str, span: Span) -> Result<usize, EvaluatorError> {
+        if value > u16::MAX as usize {
+            return Err(EvaluatorError::new(
+                EvaluatorErrorKind::LimitExceeded,
+                format!("Too many {what} to compile, the limit is {}", u16::MAX),
+            )
+            .with_span(span));
+        }
+
+        Ok(value)
+    }
+}
+
+fn infix_opcode(operator: &Token) -> Opcode {
+    match operator {
+        token![+] => Opcode::Add,
+        token![-] => Opcode::Sub,
+        token![*] => Opcode::Mul,
+        token![/] => Opcode::Div,
+        token![%] => Opcode::Mod,
+        token![**] => Opcode::Pow,
+        token![==] => Opcode::Equal,
+        token![!=] => Opcode::NotEqual,
+        token![<] => Opcode::LessThan,
+        token![>] => Opcode::GreaterThan,
+        token![<=] => Opcode::LessEqual,
+        token![>=] => Opcode::GreaterEqual,
+        _ => unreachable!("the parser only produces infix expressions for infix operators"),
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolScope {
+    Global,
+    Local,
+    // A local of an enclosing function or loop body, `depth` scopes out from the current one
+    Free { depth: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Symbol {
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+// One table per function or for loop body being compiled, the outermost table holds the
+// globals
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    store: HashMap<String, usize>,
+    // Globals that have a slot but are not defined by any program yet, like the names that
+    // are only looked up at runtime. They are left to the lookups until a `let` defines them
+    reserved: HashMap<String, usize>,
+    // Names in definition order so that globals can be reported by name at runtime
+    names: Vec<String>,
+    outer: Option<Box<SymbolTable>>,
+}
+
+impl SymbolTable {
+    pub fn new_enclosed(outer: SymbolTable) -> Self {
+        SymbolTable {
+            outer: Some(Box::new(outer)),
+            ..Default::default()
+        }
+    }
+
+    // Returns the enclosing table, consuming this one
+    pub fn into_outer(self) -> Option<SymbolTable> {
+        self.outer.map(|outer| *outer)
+    }
+
+    fn scope(&self) -> SymbolScope {
+        match self.outer {
+            Some(_) => SymbolScope::Local,
+            None => SymbolScope::Global,
+        }
+    }
+
+    // Redefining a name in the same scope reuses its slot, just like `Environment::set`
+    pub fn define(&mut self, name: &str) -> Symbol {
+        let index = match self.store.get(name) {
+            Some(index) => *index,
+            None => {
+                let index = self.reserved.remove(name).unwrap_or_else(|| self.add_name(name));
+                self.store.insert(name.to_string(), index);
+                index
+            }
+        };
+
+        Symbol {
+            scope: self.scope(),
+            index,
+        }
+    }
+
+    // Only looks at the names defined in this table, not in the enclosing ones
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.store.get(name).map(|index| Symbol {
+            scope: self.scope(),
+            index: *index,
+        })
+    }
+
+    pub fn resolve(&self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.get(name) {
+            return Some(symbol);
+        }
+
+        let symbol = self.outer.as_ref()?.resolve(name)?;
+
+        Some(match symbol.scope {
+            SymbolScope::Global => symbol,
+            SymbolScope::Local => Symbol {
+                scope: SymbolScope::Free { depth: 1 },
+                index: symbol.index,
+            },
+            SymbolScope::Free { depth } => Symbol {
+                scope: SymbolScope::Free { depth: depth + 1 },
+                index: symbol.index,
+            },
+        })
+    }
+
+    // Returns the slot of the global with this name, reserving one if it is not defined, so
+    // that it can be defined later on (for example by a function declared after the one
+    // using it, or by the next line in the REPL)
+    pub fn define_global(&mut self, name: &str) -> Symbol {
+        if let Some(outer) = &mut self.outer {
+            return outer.define_global(name);
+        }
+
+        let index = match (self.store.get(name), self.reserved.get(name)) {
+            (Some(index), _) | (_, Some(index)) => *index,
+            (None, None) => {
+                let index = self.add_name(name);
+                self.reserved.insert(name.to_string(), index);
+                index
+            }
+        };
+
+        Symbol {
+            scope: SymbolScope::Global,
+            index,
+        }
+    }
+
+    fn add_name(&mut self, name: &str) -> usize {
+        self.names.push(name.to_string());
+        self.names.len() - 1
+    }
+
+    pub fn num_definitions(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    // The names that have been defined with their slots
+    pub fn bindings(&self) -> Vec<(String, usize)> {
+        self.store
+            .iter()
+            .map(|(name, index)| (name.clone(), *index))
+            .collect()
+    }
+}
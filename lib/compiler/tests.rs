@@ -0,0 +1,432 @@
+use std::rc::Rc;
+
+use crate::{eval::value::Value, lexer::Lexer, parser::Parser};
+
+use super::{
+    code::{disassemble, make, read_operands, Opcode},
+    symbol_table::{Symbol, SymbolScope},
+    Bytecode, Compiler,
+};
+
+fn compile(input: &str) -> Bytecode {
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program();
+    assert!(parser.errors.is_empty(), "Parser had errors");
+
+    match Compiler::new().compile(&program) {
+        Ok(bytecode) => bytecode,
+        Err(e) => panic!("Compiler error: {}", e),
+    }
+}
+
+fn test_instructions(actual: &[u8], expected: &[&str]) {
+    assert_eq!(
+        disassemble(actual),
+        expected.join("\n"),
+        "wrong instructions"
+    );
+}
+
+#[test]
+fn test_make() {
+    let tests = vec![
+        (Opcode::Constant, vec![65534], vec![Opcode::Constant as u8, 255, 254]),
+        (Opcode::Add, vec![], vec![Opcode::Add as u8]),
+        (Opcode::GetFree, vec![1, 258], vec![Opcode::GetFree as u8, 1, 1, 2]),
+    ];
+
+    for (op, operands, expected) in tests {
+        let instruction = make(op, &operands);
+        assert_eq!(instruction, expected, "wrong encoding for {}", op);
+
+        let (read, len) = read_operands(op, &instruction, 1);
+        assert_eq!(read, operands, "wrong operands decoded for {}", op);
+        assert_eq!(len, instruction.len() - 1, "wrong operand length for {}", op);
+    }
+}
+
+#[test]
+fn test_opcode_round_trip() {
+    let mut byte = 0;
+
+    while let Ok(op) = Opcode::try_from(byte) {
+        assert_eq!(op as u8, byte, "opcode table is out of order at {}", op);
+        byte += 1;
+    }
+
+    assert_eq!(Opcode::Import as u8 + 1, byte, "not every opcode can be decoded");
+}
+
+#[test]
+fn test_compile_integer_arithmetic() {
+    let bytecode = compile("1 + 2; -3 * 4; 5 ** 6");
+
+    test_instructions(
+        &bytecode.main.instructions,
+        &[
+            "0000 OpConstant 0",
+            "0003 OpConstant 1",
+            "0006 OpAdd",
+            "0007 OpPop",
+            "0008 OpConstant 2",
+            "0011 OpMinus",
+            "0012 OpConstant 3",
+            "0015 OpMul",
+            "0016 OpPop",
+            "0017 OpConstant 4",
+            "0020 OpConstant 5",
+            "0023 OpPow",
+            "0024 OpPop",
+        ],
+    );
+
+    let constants = bytecode
+        .pool
+        .borrow()
+        .constants
+        .iter()
+        .map(Value::to_string)
+        .collect::<Vec<String>>();
+    assert_eq!(constants, vec!["1", "2", "3", "4", "5", "6"]);
+}
+
+#[test]
+fn test_compile_shared_constants() {
+    let mut compiler = Compiler::new();
+    let parse = |input| Parser::new(Lexer::new(input)).parse_program();
+
+    let first = compiler.compile(&parse(r#"[1, "a", 1, "a", 1.5, 1.5]"#)).unwrap();
+    let second = compiler.compile(&parse(r#"let a = 1; "a" + "b""#)).unwrap();
+
+    // Integers and strings are only added once, also for the programs compiled later
+    test_instructions(
+        &first.main.instructions,
+        &[
+            "0000 OpConstant 0",
+            "0003 OpConstant 1",
+            "0006 OpConstant 0",
+            "0009 OpConstant 1",
+            "0012 OpConstant 2",
+            "0015 OpConstant 3",
+            "0018 OpArray 6",
+            "0021 OpPop",
+        ],
+    );
+    test_instructions(
+        &second.main.instructions,
+        &[
+            "0000 OpConstant 0",
+            "0003 OpSetGlobal 0",
+            "0006 OpConstant 1",
+            "0009 OpConstant 4",
+            "0012 OpAdd",
+            "0013 OpPop",
+        ],
+    );
+
+    // Both programs use the same pool instead of a copy of it
+    assert!(Rc::ptr_eq(&first.pool, &second.pool));
+    assert_eq!(second.pool.borrow().constants.len(), 5);
+    assert_eq!(first.pool.borrow().global_names, ["a"]);
+}
+
+#[test]
+fn test_compile_conditionals() {
+    let bytecode = compile("if true { 10 }; 3333;");
+
+    test_instructions(
+        &bytecode.main.instructions,
+        &[
+            "0000 OpTrue",
+            "0001 OpJumpNotTruthy 10",
+            "0004 OpConstant 0",
+            "0007 OpJump 11",
+            "0010 OpNull",
+            "0011 OpPop",
+            "0012 OpConstant 1",
+            "0015 OpPop",
+        ],
+    );
+}
+
+#[test]
+fn test_compile_globals_and_builtins() {
+    let bytecode = compile("let one = 1; len(one); two;");
+
+    test_instructions(
+        &bytecode.main.instructions,
+        &[
+            "0000 OpConstant 0",
+            "0003 OpSetGlobal 0",
+            "0006 OpGetName 0",
+            "0009 OpGetGlobal 0",
+            "0012 OpCall 1",
+            "0014 OpPop",
+            "0015 OpGetName 1",
+            "0018 OpPop",
+        ],
+    );
+
+    // Names that are not defined yet are looked up when the code runs, in a global that may
+    // still be defined before that and then in the builtins
+    let pool = bytecode.pool.borrow();
+    assert_eq!(pool.global_names, vec!["one", "len", "two"]);
+    assert_eq!(pool.lookups[0].name, "len");
+    assert_eq!(pool.lookups[0].slots, [Symbol { scope: SymbolScope::Global, index: 1 }]);
+    assert_eq!(pool.lookups[1].slots, [Symbol { scope: SymbolScope::Global, index: 2 }]);
+}
+
+#[test]
+fn test_compile_closures() {
+    let bytecode = compile("fn(a) { let b = a; fn(c) { a + b + c } }");
+
+    test_instructions(&bytecode.main.instructions, &["0000 OpClosure 1", "0003 OpPop"]);
+
+    let pool = bytecode.pool.borrow();
+    let inner = &pool.functions[0];
+    test_instructions(
+        &inner.instructions,
+        &[
+            "0000 OpGetName 0",
+            "0003 OpGetName 1",
+            "0006 OpAdd",
+            "0007 OpGetLocal 0",
+            "0010 OpAdd",
+            "0011 OpReturnValue",
+        ],
+    );
+
+    // The inner function could still define `a` and `b` itself, so they are looked up in
+    // every scope that defines them, innermost first, with the globals last
+    let free = |index| Symbol { scope: SymbolScope::Free { depth: 1 }, index };
+    let global = |index| Symbol { scope: SymbolScope::Global, index };
+    assert_eq!(pool.lookups[0].slots, [free(0), global(0)]);
+    assert_eq!(pool.lookups[1].slots, [free(1), global(1)]);
+
+    let outer = &pool.functions[1];
+    assert_eq!(outer.num_locals, 2);
+    test_instructions(
+        &outer.instructions,
+        &[
+            "0000 OpGetLocal 0",
+            "0003 OpSetLocal 1",
+            "0006 OpClosure 0",
+            "0009 OpReturnValue",
+        ],
+    );
+}
+
+#[test]
+fn test_compile_spans() {
+    let bytecode = compile("let x = 1;\nx + true;");
+
+    // The add instruction is located at the infix expression on the second line
+    let span = bytecode.main.span_at(10).expect("no span for OpAdd");
+    assert_eq!((span.line, span.column), (2, 1));
+    assert_eq!(span.end - span.start, 8);
+}
+
+#[test]
+fn test_compile_while_loops() {
+    let bytecode = compile("while true { break; continue; }");
+
+    test_instructions(
+        &bytecode.main.instructions,
+        &[
+            "0000 OpTrue",
+            "0001 OpJumpNotTruthy 13",
+            "0004 OpJump 13",
+            "0007 OpJump 0",
+            "0010 OpJump 0",
+            "0013 OpNull",
+            "0014 OpPop",
+        ],
+    );
+
+    // Operands left on the stack are dropped before jumping out of the loop
+    let bytecode = compile("while true { 1 + if true { break; } else { 2 }; }");
+
+    test_instructions(
+        &bytecode.main.instructions,
+        &[
+            "0000 OpTrue",
+            "0001 OpJumpNotTruthy 27",
+            "0004 OpConstant 0",
+            "0007 OpTrue",
+            "0008 OpJumpNotTruthy 19",
+            "0011 OpPop",
+            "0012 OpJump 27",
+            "0015 OpNull",
+            "0016 OpJump 22",
+            "0019 OpConstant 1",
+            "0022 OpAdd",
+            "0023 OpPop",
+            "0024 OpJump 0",
+            "0027 OpNull",
+            "0028 OpPop",
+        ],
+    );
+}
+
+#[test]
+fn test_compile_for_loops() {
+    let bytecode = compile("for (x in [1]) { break; }");
+
+    test_instructions(
+        &bytecode.main.instructions,
+        &[
+            "0000 OpConstant 0",
+            "0003 OpArray 1",
+            "0006 OpIter",
+            "0007 OpIterNext 24",
+            "0010 OpEnterScope 1",
+            "0013 OpSetLocal 0",
+            // The scope of the body is left before jumping out of it
+            "0016 OpLeaveScope",
+            "0017 OpJump 24",
+            "0020 OpLeaveScope",
+            "0021 OpJump 7",
+            "0024 OpPop",
+            "0025 OpNull",
+            "0026 OpPop",
+        ],
+    );
+
+    // The loop variable is a local of the body, the outer `x` is used again after the loop
+    let bytecode = compile("let x = 1; for (x in []) { let y = x; }; x");
+    assert_eq!(bytecode.pool.borrow().global_names, vec!["x"]);
+
+    let instructions = disassemble(&bytecode.main.instructions);
+    assert!(instructions.contains("OpEnterScope 2"), "{}", instructions);
+    assert!(instructions.contains("OpGetLocal 0\n0022 OpSetLocal 1"), "{}", instructions);
+    assert!(instructions.ends_with("OpGetGlobal 0\n0035 OpPop"), "{}", instructions);
+}
+
+#[test]
+fn test_compile_assignment() {
+    let bytecode = compile("let x = 1; x += 2;");
+
+    test_instructions(
+        &bytecode.main.instructions,
+        &[
+            "0000 OpConstant 0",
+            "0003 OpSetGlobal 0",
+            "0006 OpGetGlobal 0",
+            "0009 OpConstant 1",
+            "0012 OpAdd",
+            "0013 OpAssignGlobal 0",
+            "0016 OpGetGlobal 0",
+            "0019 OpPop",
+        ],
+    );
+
+    // Locals of an enclosing function are updated in place
+    let bytecode = compile("fn() { let x = 1; fn() { x = 2 } }");
+
+    test_instructions(
+        &bytecode.pool.borrow().functions[0].instructions,
+        &[
+            "0000 OpConstant 1",
+            "0003 OpSetFree 1 0",
+            "0007 OpGetFree 1 0",
+            "0011 OpReturnValue",
+        ],
+    );
+}
+
+#[test]
+fn test_compile_logical_operators() {
+    let bytecode = compile("true && false; true || false");
+
+    test_instructions(
+        &bytecode.main.instructions,
+        &[
+            "0000 OpTrue",
+            "0001 OpJumpNotTruthy 10",
+            "0004 OpFalse",
+            "0005 OpBang",
+            "0006 OpBang",
+            "0007 OpJump 11",
+            "0010 OpFalse",
+            "0011 OpPop",
+            "0012 OpTrue",
+            "0013 OpJumpNotTruthy 20",
+            "0016 OpTrue",
+            "0017 OpJump 23",
+            "0020 OpFalse",
+            "0021 OpBang",
+            "0022 OpBang",
+            "0023 OpPop",
+        ],
+    );
+}
+
+#[test]
+fn test_compile_interpolated_strings() {
+    let bytecode = compile(r#"let x = 1; f"x is {x}!""#);
+
+    test_instructions(
+        &bytecode.main.instructions,
+        &[
+            "0000 OpConstant 0",
+            "0003 OpSetGlobal 0",
+            "0006 OpConstant 1",
+            "0009 OpGetGlobal 0",
+            "0012 OpConstant 2",
+            "0015 OpConcat 3",
+            "0018 OpPop",
+        ],
+    );
+}
+
+#[test]
+fn test_compile_imports() {
+    let dir = std::env::temp_dir().join(format!("monkey-compile-imports-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("lib.mk"), "let x = 1; let f = fn() { x };").unwrap();
+
+    let mut compiler = Compiler::new();
+    compiler.set_import_dir(&dir);
+
+    let program = Parser::new(Lexer::new(
+        r#"import "lib.mk" as lib; import "./lib.mk" as again; lib.x"#,
+    ))
+    .parse_program();
+    let bytecode = compiler.compile(&program).unwrap();
+
+    // The module is only compiled once and its name is a constant
+    test_instructions(
+        &bytecode.main.instructions,
+        &[
+            "0000 OpImport 0",
+            "0003 OpSetGlobal 0",
+            "0006 OpImport 0",
+            "0009 OpSetGlobal 1",
+            "0012 OpGetGlobal 0",
+            "0015 OpMember 1",
+            "0018 OpPop",
+        ],
+    );
+    let pool = bytecode.pool.borrow();
+    assert_eq!(pool.modules.len(), 1);
+
+    // The top level of the module has globals of its own and returns once it has run
+    let module = &pool.modules[0];
+    test_instructions(
+        &module.main.instructions,
+        &[
+            "0000 OpConstant 0",
+            "0003 OpSetGlobal 0",
+            "0006 OpClosure 0",
+            "0009 OpSetGlobal 1",
+            "0012 OpNull",
+            "0013 OpReturnValue",
+        ],
+    );
+    assert_eq!(module.global_names, ["x", "f"]);
+    assert_eq!(module.main.module, Some(0));
+    assert_eq!(pool.functions[0].module, Some(0));
+    assert!(matches!(&pool.constants[1], Value::String(name) if name == "x"));
+}
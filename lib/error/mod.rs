@@ -0,0 +1,259 @@
+use std::fmt::{Debug, Display};
+
+use crate::token::{Span, Token};
+
+#[cfg(test)]
+mod tests;
+
+// ANSI escape codes used when rendering diagnostics to a terminal
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+// A single error produced while lexing, parsing or evaluating a program
+//
+// `kind` lets embedders match on what went wrong without inspecting `message`,
+// and `notes` hold extra context that is shown below the message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic<K> {
+    pub kind: K,
+    pub message: String,
+    pub span: Option<Span>,
+    pub notes: Vec<String>,
+}
+
+impl<K> Diagnostic<K> {
+    pub fn new(kind: K, message: impl Into<String>) -> Self {
+        Diagnostic {
+            kind,
+            message: message.into(),
+            span: None,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    // Only sets the span if it has not been set already so that the innermost location is kept
+    pub fn or_span(mut self, span: Span) -> Self {
+        self.span.get_or_insert(span);
+        self
+    }
+
+    // Renders the error like rustc does, with the offending source line and the span underlined
+    //
+    // error: Invalid infix expression: (1 + true)
+    //  --> script.mk:2:5
+    //   |
+    // 2 |     x + y
+    //   |     ^^^^^
+    //   = note: ...
+    pub fn render(&self, source: &str, file_name: &str, color: bool) -> String {
+        let paint = |code: &'static str| if color { code } else { "" };
+        let (red, blue, bold, reset) = (paint(RED), paint(BLUE), paint(BOLD), paint(RESET));
+
+        let mut output = format!("{red}error{reset}{bold}: {}{reset}\n", self.message);
+
+        let Some(span) = self.span else {
+            output.push_str(&format!(" {blue}-->{reset} {file_name}\n"));
+            for note in &self.notes {
+                output.push_str(&format!("  {blue}={reset} {bold}note{reset}: {note}\n"));
+            }
+            return output;
+        };
+
+        let line_number = span.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        output.push_str(&format!("{gutter}{blue}-->{reset} {file_name}:{span}\n"));
+
+        // The span can come from an earlier source, like a function defined on a previous REPL
+        // line, so only show a snippet if the span actually points into this one
+        if Self::span_fits(span, source) {
+            // Find the line that the span starts on
+            let start = span.start;
+            let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+            let line = source[line_start..line_end].trim_end_matches('\r');
+
+            // Keep tabs in the padding so the carets line up with the source line
+            let padding = source[line_start..start]
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+
+            // Underline the span but never past the end of the line it starts on
+            let end = span.end.clamp(start, line_start + line.len());
+            let underline = "^".repeat(source[start..end].chars().count().max(1));
+
+            output.push_str(&format!("{gutter} {blue}|{reset}\n"));
+            output.push_str(&format!("{blue}{line_number} |{reset} {line}\n"));
+            output.push_str(&format!("{gutter} {blue}|{reset} {padding}{red}{underline}{reset}\n"));
+        }
+
+        for note in &self.notes {
+            output.push_str(&format!("{gutter} {blue}={reset} {bold}note{reset}: {note}\n"));
+        }
+
+        output
+    }
+
+    // Whether the span starts inside `source` on the line it claims to and both of its ends fall
+    // on char boundaries, an end past the source is fine since the underline is clamped anyway
+    fn span_fits(span: Span, source: &str) -> bool {
+        source.is_char_boundary(span.start)
+            && source.is_char_boundary(span.end.min(source.len()))
+            && source[..span.start].matches('\n').count() + 1 == span.line
+    }
+}
+
+impl<K> Display for Diagnostic<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(span) = self.span {
+            write!(f, "{span}: ")?;
+        }
+
+        write!(f, "{}", self.message)?;
+
+        for note in &self.notes {
+            write!(f, "\n\tnote: {note}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<K: Debug> std::error::Error for Diagnostic<K> {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexerErrorKind {
+    IllegalCharacter,
+    // A string literal that is still open at the end of the input
+    UnterminatedString,
+    // A `/*` comment that is still open at the end of the input
+    UnterminatedComment,
+    // An unknown escape like `\q` or a malformed `\u{...}` escape in a string
+    InvalidEscape,
+    // A number literal like `0b102` or `0x` with digits that are missing or invalid in its base
+    InvalidNumber,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParserErrorKind {
+    // The lexer could not turn the source into a token
+    Lexer(LexerErrorKind),
+    // A specific token was expected but a different one was found
+    UnexpectedToken { expected: Token, found: Token },
+    // The token cannot start an expression
+    ExpectedExpression(Token),
+    InvalidParameter(Token),
+    // `break` or `continue` used outside of a loop
+    OutsideLoop(Token),
+    // Only identifiers can be assigned to
+    InvalidAssignmentTarget,
+    // `import` inside of a block or function
+    NestedImport,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvaluatorErrorKind {
+    IdentifierNotFound(String),
+    // An operator was applied to values it does not support
+    TypeMismatch,
+    UnusableHashKey,
+    NotCallable,
+    WrongArgumentCount { expected: usize, given: usize },
+    // For builtins with optional arguments that take anywhere from `min` to `max` arguments
+    WrongArgumentRange { min: usize, max: usize, given: usize },
+    InvalidArgument,
+    DivisionByZero,
+    // The program is too large to be compiled to bytecode, or a value would be too large
+    LimitExceeded,
+    StackOverflow,
+    // A module could not be read or has syntax errors
+    ImportFailed,
+    // A module imports itself, directly or through other modules
+    ImportCycle,
+}
+
+pub type LexerError = Diagnostic<LexerErrorKind>;
+pub type ParserError = Diagnostic<ParserErrorKind>;
+pub type EvaluatorError = Diagnostic<EvaluatorErrorKind>;
+
+impl From<LexerError> for ParserError {
+    fn from(value: LexerError) -> Self {
+        Diagnostic {
+            kind: ParserErrorKind::Lexer(value.kind),
+            message: value.message,
+            span: value.span,
+            notes: value.notes,
+        }
+    }
+}
+
+// Any error that can be returned while running a program through `Monkey`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Parser(Vec<ParserError>),
+    Evaluator(EvaluatorError),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Parser(errors) => {
+                let output = errors
+                    .iter()
+                    .map(|err| err.to_string())
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                write!(f, "{output}")
+            }
+            Error::Evaluator(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error {
+    // Renders every diagnostic in the error, see `Diagnostic::render`
+    pub fn render(&self, source: &str, file_name: &str, color: bool) -> String {
+        match self {
+            Error::Parser(errors) => errors
+                .iter()
+                .map(|err| err.render(source, file_name, color))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Error::Evaluator(err) => err.render(source, file_name, color),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parser(errors) => errors.first().map(|err| err as _),
+            Error::Evaluator(err) => Some(err),
+        }
+    }
+}
+
+impl From<Vec<ParserError>> for Error {
+    fn from(value: Vec<ParserError>) -> Self {
+        Error::Parser(value)
+    }
+}
+
+impl From<EvaluatorError> for Error {
+    fn from(value: EvaluatorError) -> Self {
+        Error::Evaluator(value)
+    }
+}
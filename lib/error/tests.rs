@@ -0,0 +1,77 @@
+use crate::token::Span;
+
+use super::{EvaluatorError, EvaluatorErrorKind, ParserError, ParserErrorKind};
+
+#[test]
+fn test_render_diagnostic() {
+    let source = "let add = fn(x, y) {\n    x + y\n};\nadd(1, true);";
+
+    let error = EvaluatorError::new(
+        EvaluatorErrorKind::TypeMismatch,
+        "Invalid infix expression: (1 + true)",
+    )
+    .with_span(Span { start: 25, end: 30, line: 2, column: 5 })
+    .with_note("+ is not supported between integer and boolean");
+
+    let expected = "\
+error: Invalid infix expression: (1 + true)
+ --> script.mk:2:5
+  |
+2 |     x + y
+  |     ^^^^^
+  = note: + is not supported between integer and boolean
+";
+
+    assert_eq!(error.render(source, "script.mk", false), expected);
+}
+
+#[test]
+fn test_render_diagnostic_clamps_to_line() {
+    let source = "let f = fn() {\n\tlet = 1;\n};";
+
+    // A span that runs onto the next line is only underlined up to the end of its first line
+    let error = ParserError::new(ParserErrorKind::ExpectedExpression(token![=]), "bad")
+        .with_span(Span { start: 16, end: 40, line: 2, column: 2 });
+
+    let expected = "\
+error: bad
+ --> <repl>:2:2
+  |
+2 | \tlet = 1;
+  | \t^^^^^^^^
+";
+
+    assert_eq!(error.render(source, "<repl>", false), expected);
+}
+
+#[test]
+fn test_render_diagnostic_color() {
+    let error = EvaluatorError::new(EvaluatorErrorKind::NotCallable, "oops")
+        .with_span(Span { start: 0, end: 1, line: 1, column: 1 });
+
+    let rendered = error.render("5()", "a.mk", true);
+
+    assert!(rendered.starts_with("\x1b[1;31merror\x1b[0m"), "got {:?}", rendered);
+    assert!(rendered.contains("\x1b[1;31m^\x1b[0m"), "got {:?}", rendered);
+}
+
+#[test]
+fn test_render_diagnostic_span_from_other_source() {
+    let error = EvaluatorError::new(EvaluatorErrorKind::DivisionByZero, "division by zero")
+        .with_span(Span { start: 21, end: 26, line: 1, column: 22 })
+        .with_note("x");
+
+    // The span is not on a char boundary of this source, so only its location is shown
+    let expected = "\
+error: division by zero
+ --> <repl>:1:22
+  = note: x
+";
+
+    assert_eq!(error.render(r#"f("ééééééééééééé")"#, "<repl>", false), expected);
+
+    // Same for a span that starts past the end of the source or on a line that it does not have
+    assert_eq!(error.render("f()", "<repl>", false), expected);
+    let error = error.with_span(Span { start: 0, end: 1, line: 2, column: 1 });
+    assert!(!error.render("f()", "<repl>", false).contains('|'));
+}
@@ -0,0 +1,302 @@
+use std::collections::{BTreeMap, HashMap};
+
+use num_bigint::BigInt;
+
+use crate::error::{EvaluatorError, EvaluatorErrorKind};
+
+use super::{
+    ops::big_integer_to_float,
+    value::{HashKey, Value},
+};
+
+// Conversions between Rust values and `Value` for programs embedding the interpreter
+//
+// Tuples are arrays of the same length and `Option` is null when it is `None`
+
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self, EvaluatorError>;
+}
+
+// The arguments of a script function called from Rust, see `Monkey::call`
+pub trait IntoArgs {
+    fn into_args(self) -> Vec<Value>;
+}
+
+fn expected(what: &str, value: &Value) -> EvaluatorError {
+    EvaluatorError::new(
+        EvaluatorErrorKind::TypeMismatch,
+        format!("expected {what}, got {value}"),
+    )
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self, EvaluatorError> {
+        Ok(value)
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Null
+    }
+}
+
+impl FromValue for () {
+    fn from_value(value: Value) -> Result<Self, EvaluatorError> {
+        match value {
+            Value::Null => Ok(()),
+            value => Err(expected("null", &value)),
+        }
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Boolean(self)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Result<Self, EvaluatorError> {
+        match value {
+            Value::Boolean(b) => Ok(b),
+            value => Err(expected("a boolean", &value)),
+        }
+    }
+}
+
+// Integers too large for an i64 become a Value::BigInt
+macro_rules! integer_conversions {
+    ($($int:ty),*) => {$(
+        impl IntoValue for $int {
+            fn into_value(self) -> Value {
+                match i64::try_from(self) {
+                    Ok(i) => Value::Integer(i),
+                    Err(_) => Value::BigInt(BigInt::from(self)),
+                }
+            }
+        }
+
+        impl FromValue for $int {
+            fn from_value(value: Value) -> Result<Self, EvaluatorError> {
+                let converted = match &value {
+                    Value::Integer(i) => <$int>::try_from(*i).ok(),
+                    Value::BigInt(i) => <$int>::try_from(i).ok(),
+                    _ => return Err(expected("an integer", &value)),
+                };
+
+                converted.ok_or_else(|| {
+                    EvaluatorError::new(
+                        EvaluatorErrorKind::LimitExceeded,
+                        format!("{value} does not fit in {}", stringify!($int)),
+                    )
+                })
+            }
+        }
+    )*};
+}
+
+integer_conversions!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl IntoValue for BigInt {
+    fn into_value(self) -> Value {
+        Value::from(self)
+    }
+}
+
+impl FromValue for BigInt {
+    fn from_value(value: Value) -> Result<Self, EvaluatorError> {
+        match value {
+            Value::Integer(i) => Ok(BigInt::from(i)),
+            Value::BigInt(i) => Ok(i),
+            value => Err(expected("an integer", &value)),
+        }
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+// Integers are accepted where a float is expected, like in arithmetic
+impl FromValue for f64 {
+    fn from_value(value: Value) -> Result<Self, EvaluatorError> {
+        match value {
+            Value::Integer(i) => Ok(i as f64),
+            Value::BigInt(i) => Ok(big_integer_to_float(&i)),
+            Value::Float(f) => Ok(f),
+            value => Err(expected("a number", &value)),
+        }
+    }
+}
+
+impl IntoValue for f32 {
+    fn into_value(self) -> Value {
+        Value::Float(self.into())
+    }
+}
+
+impl FromValue for f32 {
+    fn from_value(value: Value) -> Result<Self, EvaluatorError> {
+        f64::from_value(value).map(|f| f as f32)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Result<Self, EvaluatorError> {
+        match value {
+            Value::String(s) => Ok(s),
+            value => Err(expected("a string", &value)),
+        }
+    }
+}
+
+impl IntoValue for char {
+    fn into_value(self) -> Value {
+        Value::from(self)
+    }
+}
+
+impl FromValue for char {
+    fn from_value(value: Value) -> Result<Self, EvaluatorError> {
+        if let Value::String(s) = &value {
+            let mut chars = s.chars();
+            if let (Some(c), None) = (chars.next(), chars.next()) {
+                return Ok(c);
+            }
+        }
+
+        Err(expected("a single character", &value))
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        self.map_or(Value::Null, T::into_value)
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value) -> Result<Self, EvaluatorError> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::Array(self.into_iter().map(T::into_value).collect())
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Result<Self, EvaluatorError> {
+        match value {
+            Value::Array(elements) => elements.into_iter().map(T::from_value).collect(),
+            value => Err(expected("an array", &value)),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for HashMap<String, T> {
+    fn into_value(self) -> Value {
+        let pairs = self
+            .into_iter()
+            .map(|(key, value)| (HashKey::String(key), value.into_value()))
+            .collect::<BTreeMap<HashKey, Value>>();
+
+        Value::Hash(pairs)
+    }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn from_value(value: Value) -> Result<Self, EvaluatorError> {
+        let Value::Hash(pairs) = value else {
+            return Err(expected("a hash", &value));
+        };
+
+        pairs
+            .into_iter()
+            .map(|(key, value)| match key {
+                HashKey::String(key) => Ok((key, T::from_value(value)?)),
+                key => Err(expected("a string key", &Value::from(key))),
+            })
+            .collect()
+    }
+}
+
+impl IntoArgs for () {
+    fn into_args(self) -> Vec<Value> {
+        Vec::new()
+    }
+}
+
+// For calls where the number of arguments is only known at runtime
+impl IntoArgs for Vec<Value> {
+    fn into_args(self) -> Vec<Value> {
+        self
+    }
+}
+
+macro_rules! tuple_conversions {
+    ($len:literal: $($name:ident),*) => {
+        impl<$($name: IntoValue),*> IntoValue for ($($name,)*) {
+            fn into_value(self) -> Value {
+                Value::Array(self.into_args())
+            }
+        }
+
+        impl<$($name: FromValue),*> FromValue for ($($name,)*) {
+            fn from_value(value: Value) -> Result<Self, EvaluatorError> {
+                match value {
+                    Value::Array(elements) if elements.len() == $len => {
+                        let mut elements = elements.into_iter();
+                        Ok(($($name::from_value(elements.next().unwrap())?,)*))
+                    }
+                    value => Err(expected(concat!("an array of ", $len, " elements"), &value)),
+                }
+            }
+        }
+
+        impl<$($name: IntoValue),*> IntoArgs for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn into_args(self) -> Vec<Value> {
+                let ($($name,)*) = self;
+                vec![$($name.into_value()),*]
+            }
+        }
+    };
+}
+
+tuple_conversions!(1: A);
+tuple_conversions!(2: A, B);
+tuple_conversions!(3: A, B, C);
+tuple_conversions!(4: A, B, C, D);
+tuple_conversions!(5: A, B, C, D, E);
+tuple_conversions!(6: A, B, C, D, E, F);
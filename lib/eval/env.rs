@@ -1,27 +1,58 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
 
-use super::{value::Value, EvaluatorErr};
+use super::value::Value;
+use crate::error::{EvaluatorError, EvaluatorErrorKind};
 
 #[derive(Debug, Default, Clone)]
 pub struct Environment {
     map: HashMap<String, Value>,
     outer: Option<Rc<RefCell<Environment>>>,
+    // The path of the module this is the top level of, set on outermost environments only
+    module: Option<String>,
 }
 
 impl Environment {
     pub fn new_enclosed(outer: Rc<RefCell<Environment>>) -> Rc<RefCell<Self>> {
         Rc::new(RefCell::new(Environment {
             outer: Some(outer),
-            map: HashMap::new(),
+            ..Default::default()
         }))
     }
 
-    pub fn get(&self, name: &String) -> Result<Value, EvaluatorErr> {
+    // The top level of an imported module
+    pub fn new_module(path: &str) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Environment {
+            module: Some(path.to_string()),
+            ..Default::default()
+        }))
+    }
+
+    // The path of the module the code using this environment was written in, `None` for
+    // the main program
+    pub fn module(&self) -> Option<String> {
+        match &self.outer {
+            Some(outer) => outer.borrow().module(),
+            None => self.module.clone(),
+        }
+    }
+
+    pub fn bindings(&self) -> BTreeMap<String, Value> {
+        self.map
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    pub fn get(&self, name: &String) -> Result<Value, EvaluatorError> {
         match self.map.get(name) {
             Some(v) => Ok(v.clone()),
             None => match &self.outer {
                 Some(outer) => outer.borrow().get(name),
-                None => Err(format!("identifier not found: {name}")),
+                None => Err(identifier_not_found(name)),
             },
         }
     }
@@ -30,4 +61,26 @@ impl Environment {
         self.map.insert(name, value.clone());
         value
     }
+
+    // Updates the binding in the scope that defined it, unlike `set` which always
+    // defines the name in this scope
+    pub fn assign(&mut self, name: &String, value: Value) -> Result<Value, EvaluatorError> {
+        if let Some(slot) = self.map.get_mut(name) {
+            *slot = value.clone();
+            return Ok(value);
+        }
+
+        match &self.outer {
+            Some(outer) => outer.borrow_mut().assign(name, value),
+            None => Err(identifier_not_found(name)),
+        }
+    }
+}
+
+pub fn identifier_not_found(name: &str) -> EvaluatorError {
+    EvaluatorError::new(
+        EvaluatorErrorKind::IdentifierNotFound(name.to_string()),
+        format!("identifier not found: {name}"),
+    )
+    .with_note(format!("define it with `let {name} = ...;` before using it"))
 }
@@ -1,33 +1,908 @@
-use super::{value::Value, EvaluatorErr};
+use super::{
+    ops::{self, equals, unusable_hash_key},
+    random::Random,
+    value::{Range, Truth, Value},
+};
+use crate::{
+    error::{EvaluatorError, EvaluatorErrorKind},
+    token::Token,
+};
 
-use std::io::{stdin, stdout, Write};
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, Signed, Zero};
 
-pub type BuiltInFunctionType = fn(Vec<Value>) -> Result<Value, EvaluatorErr>;
+use std::{
+    f64::consts,
+    fmt::Debug,
+    io::{stdin, stdout, Write},
+};
 
-pub fn get_function(name: &str) -> Option<BuiltInFunctionType> {
+pub type BuiltInFunctionType = fn(Vec<Value>) -> Result<Value, EvaluatorError>;
+// Builtins that use the random number generator of the interpreter calling them
+pub type RandomFunctionType = fn(&mut Random, Vec<Value>) -> Result<Value, EvaluatorError>;
+pub type NativeFunctionType = Box<dyn Fn(&[Value]) -> Result<Value, EvaluatorError>>;
+
+#[derive(Debug, Clone, Copy)]
+pub enum BuiltIn {
+    Pure(BuiltInFunctionType),
+    Random(RandomFunctionType),
+}
+
+impl BuiltIn {
+    pub fn call(self, random: &mut Random, args: Vec<Value>) -> Result<Value, EvaluatorError> {
+        match self {
+            BuiltIn::Pure(func) => func(args),
+            BuiltIn::Random(func) => func(random, args),
+        }
+    }
+}
+
+impl PartialEq for BuiltIn {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (BuiltIn::Pure(l), BuiltIn::Pure(r)) => std::ptr::fn_addr_eq(*l, *r),
+            (BuiltIn::Random(l), BuiltIn::Random(r)) => std::ptr::fn_addr_eq(*l, *r),
+            _ => false,
+        }
+    }
+}
+
+// A function registered by the program embedding the interpreter
+//
+// The closure can capture any state the host wants to share with scripts, like an
+// `Rc<RefCell<_>>` that the host keeps a clone of
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    func: NativeFunctionType,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: &str,
+        arity: usize,
+        func: impl Fn(&[Value]) -> Result<Value, EvaluatorError> + 'static,
+    ) -> Self {
+        NativeFunction {
+            name: name.to_string(),
+            arity,
+            func: Box::new(func),
+        }
+    }
+
+    // The closure is only called with exactly `arity` arguments
+    pub fn call(&self, args: &[Value]) -> Result<Value, EvaluatorError> {
+        if args.len() != self.arity {
+            return Err(wrong_argument_count(&self.name, self.arity, args.len()));
+        }
+
+        (self.func)(args)
+    }
+}
+
+impl Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish()
+    }
+}
+
+pub fn get_function(name: &str) -> Option<BuiltIn> {
+    match name {
+        "random" => Some(BuiltIn::Random(random)),
+        "random_int" => Some(BuiltIn::Random(random_int)),
+        "shuffle" => Some(BuiltIn::Random(shuffle)),
+        "choice" => Some(BuiltIn::Random(choice)),
+        "seed" => Some(BuiltIn::Random(seed)),
+        _ => get_pure_function(name).map(BuiltIn::Pure),
+    }
+}
+
+fn get_pure_function(name: &str) -> Option<BuiltInFunctionType> {
     match name {
         "len" => Some(len),
+        "first" => Some(first),
+        "last" => Some(last),
+        "rest" => Some(rest),
+        "push" => Some(push),
+        "keys" => Some(keys),
+        "values" => Some(values),
+        "has_key" => Some(has_key),
+        "delete" => Some(delete),
+        "range" => Some(range),
+        "int" => Some(int),
+        "float" => Some(float),
+        "round" => Some(round),
+        "floor" => Some(floor),
+        "ceil" => Some(ceil),
+        "abs" => Some(abs),
+        "min" => Some(min),
+        "max" => Some(max),
+        "pow" => Some(pow),
+        "sqrt" => Some(sqrt),
+        "exp" => Some(exp),
+        "log" => Some(log),
+        "sin" => Some(sin),
+        "cos" => Some(cos),
+        "tan" => Some(tan),
+        "asin" => Some(asin),
+        "acos" => Some(acos),
+        "atan" => Some(atan),
+        "atan2" => Some(atan2),
+        "gcd" => Some(gcd),
+        "lcm" => Some(lcm),
+        "split" => Some(split),
+        "join" => Some(join),
+        "trim" => Some(trim),
+        "upper" => Some(upper),
+        "lower" => Some(lower),
+        "replace" => Some(replace),
+        "contains" => Some(contains),
+        "starts_with" => Some(starts_with),
+        "ends_with" => Some(ends_with),
+        "index_of" => Some(index_of),
+        "substr" => Some(substr),
+        "slice" => Some(slice),
+        "repeat" => Some(repeat),
+        "chars" => Some(chars),
+        "format" => Some(format),
         "println" => Some(println),
         "input" => Some(input),
         _ => None,
     }
 }
 
-fn len(args: Vec<Value>) -> Result<Value, EvaluatorErr> {
+// Constants that are available everywhere like the builtin functions
+pub fn get_constant(name: &str) -> Option<Value> {
+    match name {
+        "PI" => Some(Value::Float(consts::PI)),
+        "TAU" => Some(Value::Float(consts::TAU)),
+        "E" => Some(Value::Float(consts::E)),
+        _ => None,
+    }
+}
+
+fn wrong_argument_count(name: &str, expected: usize, given: usize) -> EvaluatorError {
+    let plural = if expected == 1 { "argument" } else { "arguments" };
+
+    EvaluatorError::new(
+        EvaluatorErrorKind::WrongArgumentCount { expected, given },
+        format!("{name}() takes {expected} {plural}, {given} arguments given"),
+    )
+}
+
+// For builtins with optional arguments
+fn check_argument_count(
+    name: &str,
+    args: &[Value],
+    min: usize,
+    max: usize,
+) -> Result<(), EvaluatorError> {
+    match args.len() {
+        given if (min..=max).contains(&given) => Ok(()),
+        given if min == max => Err(wrong_argument_count(name, min, given)),
+        given => Err(EvaluatorError::new(
+            EvaluatorErrorKind::WrongArgumentRange { min, max, given },
+            format!("{name}() takes {min} to {max} arguments, {given} arguments given"),
+        )),
+    }
+}
+
+fn invalid_argument(message: String) -> EvaluatorError {
+    EvaluatorError::new(EvaluatorErrorKind::InvalidArgument, message)
+}
+
+// The error for an argument of the wrong type, `position` starts at 0
+fn expected_argument(name: &str, position: usize, expected: &str, arg: &Value) -> EvaluatorError {
+    invalid_argument(format!(
+        "{name}() expects argument {} to be {expected}, got {arg}",
+        position + 1
+    ))
+}
+
+fn string_arg<'a>(
+    name: &str,
+    args: &'a [Value],
+    position: usize,
+) -> Result<&'a str, EvaluatorError> {
+    match &args[position] {
+        Value::String(s) => Ok(s),
+        arg => Err(expected_argument(name, position, "a string", arg)),
+    }
+}
+
+fn integer_arg(name: &str, args: &[Value], position: usize) -> Result<i64, EvaluatorError> {
+    match &args[position] {
+        Value::Integer(i) => Ok(*i),
+        arg => Err(expected_argument(name, position, "an integer", arg)),
+    }
+}
+
+fn len(args: Vec<Value>) -> Result<Value, EvaluatorError> {
     if args.len() == 1 {
         match args.first().unwrap() {
-            Value::String(s) => Ok(Value::Integer(s.len() as i64)),
-            _ => Err(format!("Cannot take length of {}", args.first().unwrap())),
+            // Strings are measured in characters, not bytes
+            Value::String(s) => Ok(Value::Integer(s.chars().count() as i64)),
+            Value::Array(elements) => Ok(Value::Integer(elements.len() as i64)),
+            _ => Err(invalid_argument(format!("Cannot take length of {}", args.first().unwrap()))),
         }
     } else {
-        return Err(format!(
-            "len() takes 1 argument, {} arguments given",
-            args.len()
-        ));
+        Err(wrong_argument_count("len", 1, args.len()))
     }
 }
 
-fn println(args: Vec<Value>) -> Result<Value, EvaluatorErr> {
+fn first(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    if args.len() == 1 {
+        match args.first().unwrap() {
+            Value::Array(elements) => Ok(elements.first().cloned().unwrap_or(Value::Null)),
+            Value::String(s) => Ok(s.chars().next().map_or(Value::Null, Value::from)),
+            _ => Err(invalid_argument(format!("Cannot take first of {}", args.first().unwrap()))),
+        }
+    } else {
+        Err(wrong_argument_count("first", 1, args.len()))
+    }
+}
+
+fn last(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    if args.len() == 1 {
+        match args.first().unwrap() {
+            Value::Array(elements) => Ok(elements.last().cloned().unwrap_or(Value::Null)),
+            Value::String(s) => Ok(s.chars().next_back().map_or(Value::Null, Value::from)),
+            _ => Err(invalid_argument(format!("Cannot take last of {}", args.first().unwrap()))),
+        }
+    } else {
+        Err(wrong_argument_count("last", 1, args.len()))
+    }
+}
+
+// Returns a new array containing every element except the first one, or a string without
+// its first character
+fn rest(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    if args.len() == 1 {
+        match args.first().unwrap() {
+            Value::Array(elements) if elements.is_empty() => Ok(Value::Null),
+            Value::Array(elements) => Ok(Value::Array(elements[1..].to_vec())),
+            Value::String(s) if s.is_empty() => Ok(Value::Null),
+            Value::String(s) => Ok(s.chars().skip(1).collect::<String>().into()),
+            _ => Err(invalid_argument(format!("Cannot take rest of {}", args.first().unwrap()))),
+        }
+    } else {
+        Err(wrong_argument_count("rest", 1, args.len()))
+    }
+}
+
+// Arrays are immutable so this returns a new array with the value appended
+fn push(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    if args.len() == 2 {
+        match args.first().unwrap() {
+            Value::Array(elements) => {
+                let mut elements = elements.clone();
+                elements.push(args[1].clone());
+                Ok(Value::Array(elements))
+            }
+            _ => Err(invalid_argument(format!("Cannot push to {}", args.first().unwrap()))),
+        }
+    } else {
+        Err(wrong_argument_count("push", 2, args.len()))
+    }
+}
+
+fn keys(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    if args.len() == 1 {
+        match args.first().unwrap() {
+            Value::Hash(pairs) => Ok(Value::Array(
+                pairs.keys().cloned().map(Value::from).collect(),
+            )),
+            _ => Err(invalid_argument(format!("Cannot take keys of {}", args.first().unwrap()))),
+        }
+    } else {
+        Err(wrong_argument_count("keys", 1, args.len()))
+    }
+}
+
+fn values(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    if args.len() == 1 {
+        match args.first().unwrap() {
+            Value::Hash(pairs) => Ok(Value::Array(pairs.values().cloned().collect())),
+            _ => Err(invalid_argument(format!("Cannot take values of {}", args.first().unwrap()))),
+        }
+    } else {
+        Err(wrong_argument_count("values", 1, args.len()))
+    }
+}
+
+fn has_key(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    if args.len() == 2 {
+        match (&args[0], args[1].hash_key()) {
+            (Value::Hash(pairs), Some(key)) => Ok(Value::Boolean(pairs.contains_key(&key))),
+            (Value::Hash(_), None) => Err(unusable_hash_key(&args[1])),
+            _ => Err(invalid_argument(format!("Cannot check keys of {}", args[0]))),
+        }
+    } else {
+        Err(wrong_argument_count("has_key", 2, args.len()))
+    }
+}
+
+// Hashes are immutable so this returns a new hash without the key
+fn delete(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    if args.len() == 2 {
+        match (&args[0], args[1].hash_key()) {
+            (Value::Hash(pairs), Some(key)) => {
+                let mut pairs = pairs.clone();
+                pairs.remove(&key);
+                Ok(Value::Hash(pairs))
+            }
+            (Value::Hash(_), None) => Err(unusable_hash_key(&args[1])),
+            _ => Err(invalid_argument(format!("Cannot delete from {}", args[0]))),
+        }
+    } else {
+        Err(wrong_argument_count("delete", 2, args.len()))
+    }
+}
+
+// range(end), range(start, end) or range(start, end, step), end is never included
+fn range(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    let mut numbers = Vec::new();
+    for arg in &args {
+        match arg {
+            Value::Integer(i) => numbers.push(*i),
+            _ => return Err(invalid_argument(format!("Cannot make a range with {arg}"))),
+        }
+    }
+
+    check_argument_count("range", &args, 1, 3)?;
+
+    let (start, end, step) = match numbers[..] {
+        [end] => (0, end, 1),
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step),
+        _ => unreachable!("the number of arguments has been checked"),
+    };
+
+    if step == 0 {
+        return Err(invalid_argument("range() step cannot be 0".to_string()));
+    }
+
+    Ok(Value::Range(Range { start, end, step }))
+}
+
+// Truncates floats towards zero and parses strings
+fn int(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    if args.len() == 1 {
+        let arg = args.first().unwrap();
+        match arg {
+            Value::Integer(_) | Value::BigInt(_) => Ok(arg.clone()),
+            Value::Float(f) => float_to_integer(*f, arg),
+            Value::Boolean(b) => Ok(Value::Integer(*b as i64)),
+            Value::String(s) => match s.trim().parse::<BigInt>() {
+                Ok(i) => Ok(i.into()),
+                Err(_) => Err(invalid_argument(format!("Cannot convert \"{s}\" to an integer"))),
+            },
+            _ => Err(invalid_argument(format!("Cannot convert {arg} to an integer"))),
+        }
+    } else {
+        Err(wrong_argument_count("int", 1, args.len()))
+    }
+}
+
+fn float(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    if args.len() == 1 {
+        let arg = args.first().unwrap();
+        match arg {
+            Value::Integer(i) => Ok(Value::Float(*i as f64)),
+            Value::BigInt(i) => Ok(Value::Float(ops::big_integer_to_float(i))),
+            Value::Float(f) => Ok(Value::Float(*f)),
+            Value::String(s) => match s.trim().parse::<f64>() {
+                Ok(f) => Ok(Value::Float(f)),
+                Err(_) => Err(invalid_argument(format!("Cannot convert \"{s}\" to a float"))),
+            },
+            _ => Err(invalid_argument(format!("Cannot convert {arg} to a float"))),
+        }
+    } else {
+        Err(wrong_argument_count("float", 1, args.len()))
+    }
+}
+
+// Rounds half away from zero
+fn round(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    round_with("round", args, f64::round)
+}
+
+fn floor(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    round_with("floor", args, f64::floor)
+}
+
+fn ceil(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    round_with("ceil", args, f64::ceil)
+}
+
+// Rounds a number to an integer, integers are already rounded
+fn round_with(
+    name: &str,
+    args: Vec<Value>,
+    rounding: fn(f64) -> f64,
+) -> Result<Value, EvaluatorError> {
+    if args.len() == 1 {
+        let arg = args.first().unwrap();
+        match arg {
+            Value::Integer(_) | Value::BigInt(_) => Ok(arg.clone()),
+            Value::Float(f) => float_to_integer(rounding(*f), arg),
+            _ => Err(invalid_argument(format!("Cannot {name} {arg}"))),
+        }
+    } else {
+        Err(wrong_argument_count(name, 1, args.len()))
+    }
+}
+
+// Truncates towards zero, large floats become big integers
+fn float_to_integer(f: f64, arg: &Value) -> Result<Value, EvaluatorError> {
+    match BigInt::from_f64(f) {
+        Some(i) => Ok(i.into()),
+        None => Err(invalid_argument(format!("Cannot convert {arg} to an integer"))),
+    }
+}
+
+// split(s) splits on whitespace, split(s, separator) on every separator
+fn split(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("split", &args, 1, 2)?;
+    let s = string_arg("split", &args, 0)?;
+
+    let parts: Vec<&str> = if args.len() == 1 {
+        s.split_whitespace().collect()
+    } else {
+        match string_arg("split", &args, 1)? {
+            "" => return Err(invalid_argument("split() separator cannot be empty".to_string())),
+            separator => s.split(separator).collect(),
+        }
+    };
+
+    Ok(Value::Array(parts.into_iter().map(|part| part.to_string().into()).collect()))
+}
+
+// Joins the elements of an array, which can be any value, with an optional separator
+fn join(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("join", &args, 1, 2)?;
+
+    let Value::Array(elements) = &args[0] else {
+        return Err(expected_argument("join", 0, "an array", &args[0]));
+    };
+    let separator = if args.len() == 2 { string_arg("join", &args, 1)? } else { "" };
+
+    Ok(elements
+        .iter()
+        .map(|element| element.to_string())
+        .collect::<Vec<String>>()
+        .join(separator)
+        .into())
+}
+
+fn trim(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    map_string("trim", args, |s| s.trim().to_string())
+}
+
+fn upper(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    map_string("upper", args, str::to_uppercase)
+}
+
+fn lower(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    map_string("lower", args, str::to_lowercase)
+}
+
+// Makes a new string from a single string argument
+fn map_string(
+    name: &str,
+    args: Vec<Value>,
+    mapping: fn(&str) -> String,
+) -> Result<Value, EvaluatorError> {
+    check_argument_count(name, &args, 1, 1)?;
+    Ok(mapping(string_arg(name, &args, 0)?).into())
+}
+
+// Replaces every occurrence
+fn replace(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("replace", &args, 3, 3)?;
+    let s = string_arg("replace", &args, 0)?;
+    let from = string_arg("replace", &args, 1)?;
+    let to = string_arg("replace", &args, 2)?;
+
+    Ok(s.replace(from, to).into())
+}
+
+// Looks for a substring in a string or an element in an array
+fn contains(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    Ok(find("contains", &args)?.is_some().into())
+}
+
+fn starts_with(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("starts_with", &args, 2, 2)?;
+    let s = string_arg("starts_with", &args, 0)?;
+
+    Ok(s.starts_with(string_arg("starts_with", &args, 1)?).into())
+}
+
+fn ends_with(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("ends_with", &args, 2, 2)?;
+    let s = string_arg("ends_with", &args, 0)?;
+
+    Ok(s.ends_with(string_arg("ends_with", &args, 1)?).into())
+}
+
+// Null when the string or array does not contain the value
+fn index_of(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    Ok(find("index_of", &args)?.map_or(Value::Null, |i| Value::Integer(i as i64)))
+}
+
+// The character index of the first occurrence of a substring or the index of the first
+// equal element of an array
+fn find(name: &str, args: &[Value]) -> Result<Option<usize>, EvaluatorError> {
+    check_argument_count(name, args, 2, 2)?;
+
+    match &args[0] {
+        Value::String(s) => {
+            let needle = string_arg(name, args, 1)?;
+            Ok(s.find(needle).map(|byte| s[..byte].chars().count()))
+        }
+        Value::Array(elements) => Ok(elements.iter().position(|element| equals(element, &args[1]))),
+        arg => Err(expected_argument(name, 0, "a string or an array", arg)),
+    }
+}
+
+// substr(s, start) or substr(s, start, length) in characters
+fn substr(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("substr", &args, 2, 3)?;
+    let s = string_arg("substr", &args, 0)?;
+
+    let start = integer_arg("substr", &args, 1)?;
+    let length = match args.get(2) {
+        Some(_) => integer_arg("substr", &args, 2)?,
+        None => i64::MAX,
+    };
+
+    for (what, value) in [("start", start), ("length", length)] {
+        if value < 0 {
+            return Err(invalid_argument(format!(
+                "substr() {what} cannot be negative, got {value}"
+            )));
+        }
+    }
+
+    Ok(s.chars().skip(start as usize).take(length as usize).collect::<String>().into())
+}
+
+// slice(x, start) or slice(x, start, end) of a string or an array, the end is not included
+// and negative indexes count from the end like in Python
+fn slice(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("slice", &args, 2, 3)?;
+
+    let len = match &args[0] {
+        Value::String(s) => s.chars().count(),
+        Value::Array(elements) => elements.len(),
+        arg => return Err(expected_argument("slice", 0, "a string or an array", arg)),
+    };
+
+    // Out of range indexes are clamped, so the slice is empty instead of an error
+    let clamp = |i: i64| {
+        let i = if i < 0 { len as i64 + i } else { i };
+        i.clamp(0, len as i64) as usize
+    };
+
+    let start = clamp(integer_arg("slice", &args, 1)?);
+    let end = match args.get(2) {
+        Some(_) => clamp(integer_arg("slice", &args, 2)?),
+        None => len,
+    }
+    .max(start);
+
+    match &args[0] {
+        Value::String(s) => Ok(s.chars().skip(start).take(end - start).collect::<String>().into()),
+        Value::Array(elements) => Ok(Value::Array(elements[start..end].to_vec())),
+        _ => unreachable!("the argument has been checked"),
+    }
+}
+
+// Strings longer than this are refused instead of running out of memory
+const MAX_STRING_LEN: usize = 1 << 30;
+
+fn repeat(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("repeat", &args, 2, 2)?;
+    let s = string_arg("repeat", &args, 0)?;
+
+    let count = integer_arg("repeat", &args, 1)?;
+
+    let Ok(times) = usize::try_from(count) else {
+        return Err(invalid_argument(format!("repeat() count cannot be negative, got {count}")));
+    };
+
+    match s.len().checked_mul(times) {
+        Some(len) if len <= MAX_STRING_LEN => Ok(s.repeat(times).into()),
+        _ => Err(EvaluatorError::new(
+            EvaluatorErrorKind::LimitExceeded,
+            format!("String too large: repeat() of {} bytes {count} times", s.len()),
+        )
+        .with_note(format!("the result would be longer than {MAX_STRING_LEN} bytes"))),
+    }
+}
+
+// The characters of a string as an array of strings
+fn chars(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("chars", &args, 1, 1)?;
+    let s = string_arg("chars", &args, 0)?;
+
+    Ok(Value::Array(s.chars().map(Value::from).collect()))
+}
+
+// format("{} + {} = {}", 1, 2, 3) replaces every `{}` with the next value, `{{` and `}}` are
+// literal braces like in interpolated strings
+fn format(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    if args.is_empty() {
+        return Err(wrong_argument_count("format", 1, 0));
+    }
+    let template = string_arg("format", &args, 0)?;
+
+    let mut output = String::new();
+    let mut values = args[1..].iter();
+    let mut placeholders = 0;
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                output.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                placeholders += 1;
+                if let Some(value) = values.next() {
+                    output.push_str(&value.to_string());
+                }
+            }
+            ('{' | '}', _) => {
+                return Err(invalid_argument(format!(
+                    "format() found an unmatched '{c}' in \"{template}\""
+                ))
+                .with_note("use {} for a value, and {{ or }} for a literal brace"))
+            }
+            _ => output.push(c),
+        }
+    }
+
+    if placeholders != args.len() - 1 {
+        return Err(invalid_argument(format!(
+            "format() has {placeholders} placeholders but {} values were given",
+            args.len() - 1
+        )));
+    }
+
+    Ok(output.into())
+}
+
+fn abs(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("abs", &args, 1, 1)?;
+
+    match &args[0] {
+        // The absolute value of i64::MIN does not fit in an i64
+        Value::Integer(i) => Ok(BigInt::from(*i).abs().into()),
+        Value::BigInt(i) => Ok(i.abs().into()),
+        Value::Float(f) => Ok(f.abs().into()),
+        arg => Err(expected_argument("abs", 0, "a number", arg)),
+    }
+}
+
+// min(a, b, ...) or min(array) of numbers or strings
+fn min(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    extreme("min", args, &token![<])
+}
+
+fn max(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    extreme("max", args, &token![>])
+}
+
+// The first value that no other value is `operator` than
+fn extreme(name: &str, args: Vec<Value>, operator: &Token) -> Result<Value, EvaluatorError> {
+    let values = match &args[..] {
+        [] => return Err(wrong_argument_count(name, 1, 0)),
+        [Value::Array(elements)] => elements.clone(),
+        _ => args,
+    };
+
+    let mut values = values.into_iter();
+    let Some(mut result) = values.next() else {
+        return Err(invalid_argument(format!("{name}() of an empty array")));
+    };
+
+    for value in values {
+        if ops::infix(value.clone(), operator, result.clone())?.truth() {
+            result = value;
+        }
+    }
+
+    Ok(result)
+}
+
+// The same as the `**` operator
+fn pow(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("pow", &args, 2, 2)?;
+    number_arg("pow", &args, 0)?;
+    number_arg("pow", &args, 1)?;
+
+    let mut args = args.into_iter();
+    ops::infix(args.next().unwrap(), &token![**], args.next().unwrap())
+}
+
+fn sqrt(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    float_function("sqrt", args, f64::sqrt)
+}
+
+fn exp(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    float_function("exp", args, f64::exp)
+}
+
+// log(x) is the natural logarithm, log(x, base) uses any other base
+fn log(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("log", &args, 1, 2)?;
+    let x = number_arg("log", &args, 0)?;
+
+    match args.len() {
+        1 => Ok(x.ln().into()),
+        _ => Ok(x.log(number_arg("log", &args, 1)?).into()),
+    }
+}
+
+// The trigonometric functions work in radians
+fn sin(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    float_function("sin", args, f64::sin)
+}
+
+fn cos(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    float_function("cos", args, f64::cos)
+}
+
+fn tan(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    float_function("tan", args, f64::tan)
+}
+
+fn asin(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    float_function("asin", args, f64::asin)
+}
+
+fn acos(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    float_function("acos", args, f64::acos)
+}
+
+fn atan(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    float_function("atan", args, f64::atan)
+}
+
+// The angle of the point (x, y), atan2(y, x)
+fn atan2(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("atan2", &args, 2, 2)?;
+    let y = number_arg("atan2", &args, 0)?;
+    let x = number_arg("atan2", &args, 1)?;
+
+    Ok(y.atan2(x).into())
+}
+
+// Applies a float function to any number, the result is always a float
+fn float_function(
+    name: &str,
+    args: Vec<Value>,
+    function: fn(f64) -> f64,
+) -> Result<Value, EvaluatorError> {
+    check_argument_count(name, &args, 1, 1)?;
+    Ok(function(number_arg(name, &args, 0)?).into())
+}
+
+fn number_arg(name: &str, args: &[Value], position: usize) -> Result<f64, EvaluatorError> {
+    match &args[position] {
+        arg @ (Value::Integer(_) | Value::BigInt(_) | Value::Float(_)) => Ok(ops::to_float(arg)),
+        arg => Err(expected_argument(name, position, "a number", arg)),
+    }
+}
+
+// The greatest common divisor is never negative, gcd(0, 0) is 0
+fn gcd(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    let (a, b) = integer_pair("gcd", &args)?;
+    Ok(greatest_common_divisor(a, b).into())
+}
+
+fn lcm(args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    let (a, b) = integer_pair("lcm", &args)?;
+
+    if a.is_zero() || b.is_zero() {
+        return Ok(Value::Integer(0));
+    }
+
+    let divisor = greatest_common_divisor(a.clone(), b.clone());
+    Ok(((a * b).abs() / divisor).into())
+}
+
+fn greatest_common_divisor(mut a: BigInt, mut b: BigInt) -> BigInt {
+    while !b.is_zero() {
+        (a, b) = (b.clone(), a % b);
+    }
+
+    a.abs()
+}
+
+// Two integers of any size
+fn integer_pair(name: &str, args: &[Value]) -> Result<(BigInt, BigInt), EvaluatorError> {
+    check_argument_count(name, args, 2, 2)?;
+
+    let integer = |position: usize| match &args[position] {
+        Value::Integer(i) => Ok(BigInt::from(*i)),
+        Value::BigInt(i) => Ok(i.clone()),
+        arg => Err(expected_argument(name, position, "an integer", arg)),
+    };
+
+    Ok((integer(0)?, integer(1)?))
+}
+
+// A float in [0, 1)
+fn random(random: &mut Random, args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("random", &args, 0, 0)?;
+    Ok(random.next_f64().into())
+}
+
+// An integer in [low, high], both ends are included
+fn random_int(random: &mut Random, args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("random_int", &args, 2, 2)?;
+    let low = integer_arg("random_int", &args, 0)?;
+    let high = integer_arg("random_int", &args, 1)?;
+
+    if low > high {
+        return Err(invalid_argument(format!(
+            "random_int() low cannot be larger than high, got {low} and {high}"
+        )));
+    }
+
+    // The size of the range wraps to 0 when it covers every i64
+    let size = high.abs_diff(low).wrapping_add(1);
+    Ok(Value::Integer(low.wrapping_add(random.below(size) as i64)))
+}
+
+// Arrays are immutable so this returns a shuffled copy
+fn shuffle(random: &mut Random, args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("shuffle", &args, 1, 1)?;
+    let Value::Array(elements) = &args[0] else {
+        return Err(expected_argument("shuffle", 0, "an array", &args[0]));
+    };
+
+    // Fisher-Yates
+    let mut elements = elements.clone();
+    for i in (1..elements.len()).rev() {
+        let j = random.below(i as u64 + 1) as usize;
+        elements.swap(i, j);
+    }
+
+    Ok(Value::Array(elements))
+}
+
+// A random element of an array
+fn choice(random: &mut Random, args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("choice", &args, 1, 1)?;
+    let Value::Array(elements) = &args[0] else {
+        return Err(expected_argument("choice", 0, "an array", &args[0]));
+    };
+
+    if elements.is_empty() {
+        return Err(invalid_argument("choice() of an empty array".to_string()));
+    }
+
+    Ok(elements[random.below(elements.len() as u64) as usize].clone())
+}
+
+// Makes the random builtins produce the same numbers every time the program runs
+fn seed(random: &mut Random, args: Vec<Value>) -> Result<Value, EvaluatorError> {
+    check_argument_count("seed", &args, 1, 1)?;
+    random.seed(integer_arg("seed", &args, 0)? as u64);
+
+    Ok(Value::Null)
+}
+
+fn println(args: Vec<Value>) -> Result<Value, EvaluatorError> {
     let result = args
         .iter()
         .map(|arg| arg.to_string())
@@ -39,7 +914,7 @@ fn println(args: Vec<Value>) -> Result<Value, EvaluatorErr> {
     Ok(Value::Null)
 }
 
-fn input(args: Vec<Value>) -> Result<Value, EvaluatorErr> {
+fn input(args: Vec<Value>) -> Result<Value, EvaluatorError> {
     if args.len() == 1 {
         match args.first().unwrap() {
             Value::String(s) => {
@@ -53,18 +928,20 @@ fn input(args: Vec<Value>) -> Result<Value, EvaluatorErr> {
                 // Remove trailing new line character and trim whitespace
                 input = input.trim().to_string();
 
-                if let Ok(parsed_int) = input.parse::<i64>() {
-                    Ok(Value::Integer(parsed_int))
+                if let Ok(parsed_int) = input.parse::<BigInt>() {
+                    Ok(parsed_int.into())
+                // Words like "inf" and "nan" also parse as floats but are kept as strings
+                } else if let Some(parsed_float) =
+                    input.parse::<f64>().ok().filter(|f| f.is_finite())
+                {
+                    Ok(Value::Float(parsed_float))
                 } else {
                     Ok(Value::String(input))
                 }
             }
-            _ => Err(format!("Cannot take input of {}", args.first().unwrap())),
+            _ => Err(invalid_argument(format!("Cannot take input of {}", args.first().unwrap()))),
         }
     } else {
-        return Err(format!(
-            "input() takes 1 argument, {} arguments given",
-            args.len()
-        ));
+        Err(wrong_argument_count("input", 1, args.len()))
     }
 }
@@ -1,8 +1,14 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    rc::Rc,
+};
 
 use crate::{
+    error::{EvaluatorError, EvaluatorErrorKind},
     parser::{
-        ast::{Ast, Expression, Statement},
+        ast::{Ast, Expression, ExpressionKind, Statement, StatementKind},
         program::Program,
     },
     token::Token,
@@ -10,34 +16,59 @@ use crate::{
 
 use self::value::{Truth, Value};
 use env::Environment;
-use funcs::get_function;
+use funcs::{get_constant, get_function, NativeFunction};
+use module::{error_in_module, Module, Modules};
+use random::Random;
 
+pub mod convert;
 pub mod env;
 pub mod funcs;
+pub mod module;
+pub mod ops;
+pub mod random;
 pub mod value;
 
 #[cfg(test)]
 mod tests;
 
-pub type EvaluatorErr = String;
-
 #[derive(Default)]
 pub struct Evaluator {
     env: Rc<RefCell<env::Environment>>,
+    random: Random,
+    modules: Modules<Value>,
+    natives: HashMap<String, Rc<NativeFunction>>,
 }
 
 impl Evaluator {
     pub fn new() -> Self {
-        Evaluator {
-            env: Rc::new(RefCell::new(Environment::default())),
-        }
+        Evaluator::default()
+    }
+
+    // Seeds the random builtins so they produce the same numbers on every run
+    pub fn seed(&mut self, seed: u64) {
+        self.random.seed(seed);
+    }
+
+    // Sets the directory that imports in the main program are relative to
+    pub fn set_import_dir(&mut self, dir: impl Into<PathBuf>) {
+        self.modules.dir = dir.into();
+    }
+
+    // Makes a host function callable by name. A binding with the same name in the program
+    // shadows it, and it shadows the builtin with the same name
+    pub fn register_function(&mut self, native: Rc<NativeFunction>) {
+        self.natives.insert(native.name.clone(), native);
     }
+
     // Main entry point
     pub fn eval_with_environment(
         ast: &Ast,
         env: Rc<RefCell<Environment>>,
-    ) -> Result<Value, EvaluatorErr> {
-        let mut evaluator = Evaluator { env };
+    ) -> Result<Value, EvaluatorError> {
+        let mut evaluator = Evaluator {
+            env,
+            ..Default::default()
+        };
         match ast {
             Ast::Program(prog) => evaluator.eval_program(prog),
             Ast::Statement(stmt) => evaluator.eval_statement(stmt),
@@ -46,11 +77,11 @@ impl Evaluator {
     }
 
     // Entry point method with a new Environment (no local variables set)
-    pub fn eval(ast: &Ast) -> Result<Value, EvaluatorErr> {
+    pub fn eval(ast: &Ast) -> Result<Value, EvaluatorError> {
         Evaluator::eval_with_environment(ast, Rc::new(RefCell::new(Environment::default())))
     }
 
-    pub fn eval_self(&mut self, ast: &Ast) -> Result<Value, EvaluatorErr> {
+    pub fn eval_self(&mut self, ast: &Ast) -> Result<Value, EvaluatorError> {
         match ast {
             Ast::Program(prog) => self.eval_program(prog),
             Ast::Statement(stmt) => self.eval_statement(stmt),
@@ -58,42 +89,116 @@ impl Evaluator {
         }
     }
 
-    fn eval_statement(&mut self, stmt: &Statement) -> Result<Value, EvaluatorErr> {
-        match stmt {
-            Statement::LetStatement { ident, value } => {
+    // Calls a function value from outside of a program, like `f(args)` would
+    pub fn call(&mut self, func: Value, arguments: Vec<Value>) -> Result<Value, EvaluatorError> {
+        match self.apply_function(func, arguments)? {
+            Value::Return(value) => Ok(*value),
+            value => Ok(value),
+        }
+    }
+
+    fn eval_statement(&mut self, stmt: &Statement) -> Result<Value, EvaluatorError> {
+        match &stmt.kind {
+            StatementKind::LetStatement { ident, value } => {
                 let value = self.eval_expression(value)?;
 
                 // Set the value in the map and return the value
                 Ok(self.env.borrow_mut().set(ident.to_string(), value))
             }
-            Statement::BlockStatement(statements) => self.eval_block(statements),
-            Statement::ReturnStatement(expr) => {
+            StatementKind::BlockStatement(statements) => self.eval_block(statements),
+            StatementKind::ReturnStatement(expr) => {
                 let value = self.eval_expression(expr)?;
                 Ok(Value::Return(Box::new(value)))
             }
-            Statement::ExpressionStatement(expr) => self.eval_expression(expr),
+            StatementKind::ExpressionStatement(expr) => self.eval_expression(expr),
+            StatementKind::WhileStatement { condition, body } => {
+                self.eval_while_statement(condition, body)
+            }
+            StatementKind::ForStatement {
+                ident,
+                iterable,
+                body,
+            } => self.eval_for_statement(ident, iterable, body),
+            StatementKind::BreakStatement => Ok(Value::Break),
+            StatementKind::ContinueStatement => Ok(Value::Continue),
+            StatementKind::ImportStatement { path, ident } => {
+                let module = self.import(path).map_err(|err| err.or_span(stmt.span))?;
+
+                Ok(self.env.borrow_mut().set(ident.to_string(), module))
+            }
+        }
+    }
+
+    // Runs the module in its own environment the first time it is imported
+    fn import(&mut self, path: &str) -> Result<Value, EvaluatorError> {
+        let resolved = self.modules.resolve(path)?;
+        if let Some(module) = self.modules.get(&resolved) {
+            return Ok(module);
         }
+
+        self.modules.enter(resolved.clone(), path)?;
+        let result = module::parse(&resolved, path).and_then(|program| {
+            let outer = std::mem::replace(&mut self.env, Environment::new_module(path));
+            let result = self.eval_program(&program);
+            let env = std::mem::replace(&mut self.env, outer);
+
+            result.map_err(|err| error_in_module(path, err))?;
+
+            let bindings = env.borrow().bindings();
+            Ok(Value::Module(Rc::new(Module {
+                path: path.to_string(),
+                bindings,
+            })))
+        });
+        self.modules.leave();
+
+        let module = result?;
+        self.modules.insert(resolved, module.clone());
+
+        Ok(module)
+    }
+
+    fn eval_expression(&mut self, expr: &Expression) -> Result<Value, EvaluatorError> {
+        // Errors are located at the innermost expression that produced them
+        self.eval_expression_kind(&expr.kind)
+            .map_err(|err| err.or_span(expr.span))
     }
 
-    fn eval_expression(&mut self, expr: &Expression) -> Result<Value, EvaluatorErr> {
+    fn eval_expression_kind(&mut self, expr: &ExpressionKind) -> Result<Value, EvaluatorError> {
         match expr {
-            Expression::IntExpression(i) => Ok(Value::Integer(*i)),
-            Expression::BooleanExpression(b) => Ok(Value::Boolean(*b)),
-            Expression::IdentifierExpression(s) => self.eval_identifier(s),
-            Expression::PrefixExpression { op_token, right } => {
+            ExpressionKind::IntExpression(i) => Ok(Value::Integer(*i)),
+            ExpressionKind::BigIntExpression(i) => Ok(Value::BigInt(i.clone())),
+            ExpressionKind::FloatExpression(f) => Ok(Value::Float(*f)),
+            ExpressionKind::BooleanExpression(b) => Ok(Value::Boolean(*b)),
+            ExpressionKind::IdentifierExpression(s) => self.eval_identifier(s),
+            ExpressionKind::PrefixExpression { op_token, right } => {
                 let right = self.eval_expression(right)?;
-                self.eval_prefix_expression(op_token, right)
+                ops::prefix(op_token, right)
             }
-            Expression::InfixExpression {
+            ExpressionKind::InfixExpression {
+                left,
+                op_token: op_token @ (token![&&] | token![||]),
+                right,
+            } => {
+                let left = self.eval_expression(left)?.truth();
+
+                // The right operand does not change the result
+                if left == (op_token == &token![||]) {
+                    return Ok(left.into());
+                }
+
+                Ok(self.eval_expression(right)?.truth().into())
+            }
+            ExpressionKind::InfixExpression {
                 left,
                 op_token,
                 right,
             } => {
                 let left = self.eval_expression(left)?;
                 let right = self.eval_expression(right)?;
-                self.eval_infix_expression(left, op_token, right)
+                ops::infix(left, op_token, right)
             }
-            Expression::IfExpression {
+            ExpressionKind::IfExpression {
                 condition,
                 consequence,
                 alternative,
@@ -101,12 +206,12 @@ impl Evaluator {
                 let condition = self.eval_expression(condition)?;
                 self.eval_if_expression(condition, consequence, alternative.as_deref())
             }
-            Expression::FunctionExpression { parameters, body } => Ok(Value::Function {
+            ExpressionKind::FunctionExpression { parameters, body } => Ok(Value::Function {
                 params: parameters.clone(),
                 body: body.clone(),
                 env: Rc::clone(&self.env),
             }),
-            Expression::CallExpression {
+            ExpressionKind::CallExpression {
                 function,
                 arguments,
             } => {
@@ -116,7 +221,7 @@ impl Evaluator {
                 let arguments = arguments
                     .iter()
                     .map(|arg| self.eval_expression(arg))
-                    .collect::<Result<Vec<Value>, EvaluatorErr>>()?;
+                    .collect::<Result<Vec<Value>, EvaluatorError>>()?;
 
                 let result = self.apply_function(function, arguments)?;
 
@@ -125,11 +230,42 @@ impl Evaluator {
                     _ => result,
                 })
             }
-            Expression::StringExpression(s) => Ok(Value::String(s.to_string())),
+            ExpressionKind::StringExpression(s) => Ok(Value::String(s.to_string())),
+            ExpressionKind::TemplateExpression(parts) => {
+                let mut output = String::new();
+                for part in parts {
+                    output.push_str(&self.eval_expression(part)?.to_string());
+                }
+
+                Ok(Value::String(output))
+            }
+            ExpressionKind::ArrayExpression(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|e| self.eval_expression(e))
+                    .collect::<Result<Vec<Value>, EvaluatorError>>()?;
+
+                Ok(Value::Array(elements))
+            }
+            ExpressionKind::IndexExpression { left, index } => {
+                let left = self.eval_expression(left)?;
+                let index = self.eval_expression(index)?;
+                ops::index(left, index)
+            }
+            ExpressionKind::MemberExpression { left, name } => {
+                let left = self.eval_expression(left)?;
+                ops::member(left, name)
+            }
+            ExpressionKind::HashExpression(pairs) => self.eval_hash_expression(pairs),
+            ExpressionKind::AssignExpression {
+                name,
+                op_token,
+                value,
+            } => self.eval_assign_expression(name, op_token, value),
         }
     }
 
-    fn eval_program(&mut self, program: &Program) -> Result<Value, EvaluatorErr> {
+    fn eval_program(&mut self, program: &Program) -> Result<Value, EvaluatorError> {
         let mut stmt_value = Value::Null;
 
         for stmt in &program.statments {
@@ -144,14 +280,14 @@ impl Evaluator {
         Ok(stmt_value)
     }
 
-    fn eval_block(&mut self, block: &Vec<Statement>) -> Result<Value, EvaluatorErr> {
+    fn eval_block(&mut self, block: &Vec<Statement>) -> Result<Value, EvaluatorError> {
         let mut stmt_value = Value::Null;
 
         for stmt in block {
             stmt_value = self.eval_statement(stmt)?;
 
-            // In evaluating the block statement we only want to return the Value::Return object
-            if let Value::Return(_) = stmt_value {
+            // Returns and loop control stop the block and are handled further up
+            if let Value::Return(_) | Value::Break | Value::Continue = stmt_value {
                 return Ok(stmt_value);
             }
         }
@@ -159,63 +295,93 @@ impl Evaluator {
         Ok(stmt_value)
     }
 
-    fn eval_prefix_expression(
-        &self,
-        operator: &Token,
-        right: Value,
-    ) -> Result<Value, EvaluatorErr> {
-        match (operator, &right) {
-            // Negate the truth value
-            (token![!], right) => Ok((!right.truth()).into()),
-            // Only apply the negative operator when its an integer
-            (token![-], Value::Integer(i)) => Ok(Value::Integer(-i)),
-            _ => Err(format!("Invalid prefix expression!\n\t({operator}{right})")),
+    fn eval_while_statement(
+        &mut self,
+        condition: &Expression,
+        body: &Statement,
+    ) -> Result<Value, EvaluatorError> {
+        while self.eval_expression(condition)?.truth() {
+            match self.eval_statement(body)? {
+                Value::Return(value) => return Ok(Value::Return(value)),
+                Value::Break => break,
+                _ => {}
+            }
         }
+
+        // Loops are statements so they do not produce a value
+        Ok(Value::Null)
     }
 
-    fn eval_infix_expression(
-        &self,
-        left: Value,
-        operator: &Token,
-        right: Value,
-    ) -> Result<Value, EvaluatorErr> {
-        match (&left, operator, &right) {
-            (Value::Integer(l), _, Value::Integer(r)) => {
-                self.eval_integer_infix_expression(*l, operator, *r)
+    fn eval_for_statement(
+        &mut self,
+        ident: &Token,
+        iterable: &Expression,
+        body: &Statement,
+    ) -> Result<Value, EvaluatorError> {
+        let value = self.eval_expression(iterable)?;
+        let items = ops::iterate(value).map_err(|err| err.or_span(iterable.span))?;
+
+        let outer_env = Rc::clone(&self.env);
+
+        let mut result = Ok(Value::Null);
+        for item in items {
+            // The loop variable and anything defined in the body only live for one iteration
+            self.env = Environment::new_enclosed(Rc::clone(&outer_env));
+            self.env.borrow_mut().set(ident.to_string(), item);
+
+            match self.eval_statement(body) {
+                Ok(Value::Return(value)) => {
+                    result = Ok(Value::Return(value));
+                    break;
+                }
+                Ok(Value::Break) => break,
+                Ok(_) => {}
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
             }
-            (Value::Boolean(l), token![==], Value::Boolean(r)) => Ok((l == r).into()),
-            (Value::Boolean(l), token![!=], Value::Boolean(r)) => Ok((l != r).into()),
-
-            // String concatenation
-            (Value::String(l), token![+], Value::String(r)) => Ok((l.to_owned() + r).into()),
-            (Value::String(l), token![+], Value::Integer(r)) => Ok((format!("{l}{r}")).into()),
-            (Value::Integer(l), token![+], Value::String(r)) => Ok((format!("{l}{r}")).into()),
-            _ => Err(format!(
-                "Invalid infix expression!\n({left} {operator} {right})"
-            )),
         }
+
+        self.env = outer_env;
+        result
     }
 
-    fn eval_integer_infix_expression(
-        &self,
-        left: i64,
+    fn eval_assign_expression(
+        &mut self,
+        name: &String,
         operator: &Token,
-        right: i64,
-    ) -> Result<Value, EvaluatorErr> {
-        match operator {
-            // Returns a Value::Integer
-            token![+] => Ok((left + right).into()),
-            token![-] => Ok((left - right).into()),
-            token![*] => Ok((left * right).into()),
-            token![/] => Ok((left / right).into()),
-            token![%] => Ok((left % right).into()),
-            // Returns a Value::Boolean
-            token![<] => Ok((left < right).into()),
-            token![>] => Ok((left > right).into()),
-            token![==] => Ok((left == right).into()),
-            token![!=] => Ok((left != right).into()),
-            _ => return Err(format!("Invalid integer infix operator!\n\t({left} {operator} {right}).\n {operator} is not a valid integer operator")),
+        value: &Expression,
+    ) -> Result<Value, EvaluatorError> {
+        let value = match operator.compound_operator() {
+            Some(operator) => {
+                let current = self.env.borrow().get(name)?;
+                let value = self.eval_expression(value)?;
+                ops::infix(current, &operator, value)?
+            }
+            None => self.eval_expression(value)?,
+        };
+
+        self.env.borrow_mut().assign(name, value)
+    }
+
+    fn eval_hash_expression(
+        &mut self,
+        pairs: &Vec<(Expression, Expression)>,
+    ) -> Result<Value, EvaluatorError> {
+        let mut hash = BTreeMap::new();
+
+        for (key, value) in pairs {
+            let key = self.eval_expression(key)?;
+            let Some(hash_key) = key.hash_key() else {
+                return Err(ops::unusable_hash_key(&key));
+            };
+
+            let value = self.eval_expression(value)?;
+            hash.insert(hash_key, value);
         }
+
+        Ok(Value::Hash(hash))
     }
 
     fn eval_if_expression(
@@ -223,7 +389,7 @@ impl Evaluator {
         condition: Value,
         consequence: &Statement,
         alternative: Option<&Statement>,
-    ) -> Result<Value, EvaluatorErr> {
+    ) -> Result<Value, EvaluatorError> {
         // If the condition is true then we evaluate the first block
         if condition.truth() {
             return self.eval_statement(consequence);
@@ -236,33 +402,66 @@ impl Evaluator {
         Ok(Value::Null)
     }
 
-    fn eval_identifier(&self, name: &String) -> Result<Value, EvaluatorErr> {
+    fn eval_identifier(&self, name: &String) -> Result<Value, EvaluatorError> {
         let env_indent = self.env.borrow().get(name);
 
-        // If could not find the identifier in the current environment then check the built in functions
+        // If could not find the identifier in the current environment then check the host
+        // functions and then the built in functions
         if let Err(msg) = env_indent {
-            return match get_function(name) {
-                Some(func) => Ok(Value::BuiltInFunction { func }),
-                None => Err(msg),
+            if let Some(native) = self.natives.get(name) {
+                return Ok(Value::NativeFunction(Rc::clone(native)));
+            }
+
+            return match (get_function(name), get_constant(name)) {
+                (Some(func), _) => Ok(Value::BuiltInFunction { func }),
+                (_, Some(constant)) => Ok(constant),
+                _ => Err(msg),
             };
         }
 
         env_indent
     }
 
-    fn apply_function(&self, func: Value, arguments: Vec<Value>) -> Result<Value, EvaluatorErr> {
+    fn apply_function(&mut self, func: Value, arguments: Vec<Value>) -> Result<Value, EvaluatorError> {
         match func {
-            Value::BuiltInFunction { func } => func(arguments),
+            Value::BuiltInFunction { func } => func.call(&mut self.random, arguments),
+            Value::NativeFunction(native) => native.call(&arguments),
             Value::Function { params, body, env } => {
-                let func_env = self.setup_function_env(env, params, arguments);
+                if params.len() != arguments.len() {
+                    return Err(EvaluatorError::new(
+                        EvaluatorErrorKind::WrongArgumentCount {
+                            expected: params.len(),
+                            given: arguments.len(),
+                        },
+                        format!(
+                            "fn({}) takes {} arguments, {} arguments given",
+                            params.join(", "),
+                            params.len(),
+                            arguments.len()
+                        ),
+                    ));
+                }
 
-                let result =
-                    Evaluator::eval_with_environment(&Ast::Statement(*body), func_env)?;
+                let func_env = self.setup_function_env(env, params, arguments);
 
-                Ok(result)
+                // The body runs on this evaluator so that it shares the random number generator
+                let outer = std::mem::replace(&mut self.env, func_env);
+                let result = self.eval_statement(&body);
+                let func_env = std::mem::replace(&mut self.env, outer);
+
+                result.map_err(|err| {
+                    // Only look up the modules when the error leaves the code of a module
+                    match func_env.borrow().module() {
+                        Some(module) if Some(&module) != self.env.borrow().module().as_ref() => {
+                            error_in_module(&module, err)
+                        }
+                        _ => err,
+                    }
+                })
             }
-            _ => Err(format!(
-                "apply_function had an error. func is not of type Value::Function or Value::BuiltInFunction. got {func}"
+            _ => Err(EvaluatorError::new(
+                EvaluatorErrorKind::NotCallable,
+                format!("Cannot call {func}, it is not a function"),
             )),
         }
     }
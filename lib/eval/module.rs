@@ -0,0 +1,139 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{Diagnostic, EvaluatorError, EvaluatorErrorKind},
+    lexer::Lexer,
+    parser::{program::Program, Parser},
+};
+
+use super::value::Value;
+
+// The top-level bindings of a file brought in with `import "path" as name;`
+//
+// The bindings are taken once the module has finished running, so reassigning one of its
+// globals later on only changes what the module's own functions see
+#[derive(Debug)]
+pub struct Module {
+    // The path as it was written in the import
+    pub path: String,
+    pub bindings: BTreeMap<String, Value>,
+}
+
+// Finds, reads and parses modules for both backends, which keep their own `T` for every
+// module that has been loaded
+//
+// Modules are cached by their canonical path so that a file imported from several places
+// only runs once, and the modules that are still being loaded are tracked to catch cycles
+#[derive(Debug, Default)]
+pub struct Modules<T> {
+    // The directory imports of the main program are relative to, the working directory
+    // when empty
+    pub dir: PathBuf,
+    // The modules being loaded with the path they were imported as, innermost last
+    loading: Vec<(PathBuf, String)>,
+    cache: HashMap<PathBuf, T>,
+}
+
+impl<T: Clone> Modules<T> {
+    // Imports are relative to the file they are written in
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, EvaluatorError> {
+        let dir = match self.loading.last() {
+            Some((module, _)) => module.parent().unwrap_or(Path::new("")),
+            None => &self.dir,
+        };
+
+        dir.join(path)
+            .canonicalize()
+            .map_err(|err| import_failed(path, err.to_string()))
+    }
+
+    pub fn get(&self, path: &Path) -> Option<T> {
+        self.cache.get(path).cloned()
+    }
+
+    pub fn insert(&mut self, path: PathBuf, module: T) {
+        self.cache.insert(path, module);
+    }
+
+    // Marks the module as being loaded until `leave` is called, failing if it already is
+    pub fn enter(&mut self, path: PathBuf, name: &str) -> Result<(), EvaluatorError> {
+        if let Some(start) = self.loading.iter().position(|(loading, _)| *loading == path) {
+            let cycle = self.loading[start..]
+                .iter()
+                .map(|(_, name)| format!("{name:?}"))
+                .chain([format!("{name:?}")])
+                .collect::<Vec<String>>()
+                .join(" -> ");
+
+            return Err(EvaluatorError::new(
+                EvaluatorErrorKind::ImportCycle,
+                format!("Cannot import {name:?}, it is already being imported"),
+            )
+            .with_note(format!("the modules import each other: {cycle}")));
+        }
+
+        self.loading.push((path, name.to_string()));
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        self.loading.pop();
+    }
+}
+
+pub fn parse(path: &Path, name: &str) -> Result<Program, EvaluatorError> {
+    let source = fs::read_to_string(path).map_err(|err| import_failed(name, err.to_string()))?;
+
+    let mut parser = Parser::new(Lexer::new(&source));
+    let program = parser.parse_program();
+
+    if parser.errors.is_empty() {
+        return Ok(program);
+    }
+
+    // The errors are located in the module, not in the file being rendered
+    let error = EvaluatorError::new(
+        EvaluatorErrorKind::ImportFailed,
+        format!("Cannot import {name:?}, it has syntax errors"),
+    );
+
+    Err(parser
+        .errors
+        .iter()
+        .fold(error, |error, err| error.with_note(located(name, err))))
+}
+
+// Errors raised by the code of a module are located in the module's file, which is not the
+// file being rendered. The location becomes a note so that the error can be located where
+// the module was used instead
+pub fn error_in_module(name: &str, err: EvaluatorError) -> EvaluatorError {
+    let mut notes = vec![match err.span {
+        Some(span) => format!("raised at {name}:{span}"),
+        None => format!("raised in module {name:?}"),
+    }];
+    notes.extend(err.notes);
+
+    EvaluatorError {
+        span: None,
+        notes,
+        ..err
+    }
+}
+
+fn import_failed(name: &str, reason: String) -> EvaluatorError {
+    EvaluatorError::new(
+        EvaluatorErrorKind::ImportFailed,
+        format!("Cannot import {name:?}: {reason}"),
+    )
+}
+
+fn located<K>(name: &str, err: &Diagnostic<K>) -> String {
+    match err.span {
+        Some(span) => format!("{name}:{span}: {}", err.message),
+        None => format!("{name}: {}", err.message),
+    }
+}
@@ -0,0 +1,353 @@
+use std::{cmp::Ordering, fmt::Display, rc::Rc};
+
+use num_bigint::BigInt;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use crate::{
+    error::{EvaluatorError, EvaluatorErrorKind},
+    token::Token,
+};
+
+use super::value::{Truth, Value, ValueIter};
+
+// The semantics of every operator live here so that the tree-walking `Evaluator`
+// and the bytecode `Vm` always agree on what an expression evaluates to
+
+pub fn prefix(operator: &Token, right: Value) -> Result<Value, EvaluatorError> {
+    match (operator, &right) {
+        // Negate the truth value
+        (token![!], right) => Ok((!right.truth()).into()),
+        // Only apply the negative operator when its an integer
+        (token![-], Value::Integer(i)) => match i.checked_neg() {
+            Some(i) => Ok(Value::Integer(i)),
+            None => Ok((-BigInt::from(*i)).into()),
+        },
+        (token![-], Value::BigInt(i)) => Ok((-i).into()),
+        (token![-], Value::Float(f)) => Ok(Value::Float(-f)),
+        _ => Err(EvaluatorError::new(
+            EvaluatorErrorKind::TypeMismatch,
+            format!("Invalid prefix expression: ({operator}{right})"),
+        )),
+    }
+}
+
+pub fn infix(left: Value, operator: &Token, right: Value) -> Result<Value, EvaluatorError> {
+    match operator {
+        token![==] => return Ok(equals(&left, &right).into()),
+        token![!=] => return Ok((!equals(&left, &right)).into()),
+        token![<] | token![>] | token![<=] | token![>=] => return compare(&left, operator, &right),
+        _ => {}
+    }
+
+    match (&left, operator, &right) {
+        (Value::Integer(l), _, Value::Integer(r)) => integer_infix(*l, operator, *r),
+        (Value::Integer(_) | Value::BigInt(_), _, Value::Integer(_) | Value::BigInt(_)) => {
+            big_integer_infix(to_big_integer(&left), operator, to_big_integer(&right))
+        }
+        // Mixing integers and floats makes a float
+        (Value::Float(_), _, Value::Integer(_) | Value::BigInt(_) | Value::Float(_))
+        | (Value::Integer(_) | Value::BigInt(_), _, Value::Float(_)) => {
+            float_infix(to_float(&left), operator, to_float(&right))
+        }
+
+        // String concatenation
+        (Value::String(l), token![+], Value::String(r)) => Ok((l.to_owned() + r).into()),
+        (Value::String(_), token![+], Value::Integer(_) | Value::BigInt(_) | Value::Float(_))
+        | (Value::Integer(_) | Value::BigInt(_) | Value::Float(_), token![+], Value::String(_)) => {
+            Ok((format!("{left}{right}")).into())
+        }
+        _ => Err(EvaluatorError::new(
+            EvaluatorErrorKind::TypeMismatch,
+            format!("Invalid infix expression: ({left} {operator} {right})"),
+        )),
+    }
+}
+
+fn integer_infix(left: i64, operator: &Token, right: i64) -> Result<Value, EvaluatorError> {
+    let checked = match operator {
+        token![+] => left.checked_add(right),
+        token![-] => left.checked_sub(right),
+        token![*] => left.checked_mul(right),
+        token![/] | token![%] if right == 0 => {
+            return Err(division_by_zero(format!("({left} {operator} {right})")))
+        }
+        token![/] => left.checked_div(right),
+        // The remainder always fits, only i64::MIN % -1 would overflow while computing it
+        token![%] => Some(left.wrapping_rem(right)),
+        // A negative exponent makes a fraction
+        token![**] if right < 0 => return float_infix(left as f64, operator, right as f64),
+        token![**] => u32::try_from(right).ok().and_then(|exp| left.checked_pow(exp)),
+        _ => return Err(invalid_operator("integer", &left, operator, &right)),
+    };
+
+    // Results that do not fit in an i64 are promoted to a big integer
+    match checked {
+        Some(result) => Ok(result.into()),
+        None => big_integer_infix(left.into(), operator, right.into()),
+    }
+}
+
+// Division truncates and the remainder has the sign of the left operand, just like for i64
+fn big_integer_infix(left: BigInt, operator: &Token, right: BigInt) -> Result<Value, EvaluatorError> {
+    match operator {
+        // Returns a Value::Integer when the result fits in an i64 again
+        token![+] => Ok((left + right).into()),
+        token![-] => Ok((left - right).into()),
+        token![*] => Ok((left * right).into()),
+        token![/] | token![%] if right.is_zero() => {
+            Err(division_by_zero(format!("({left} {operator} {right})")))
+        }
+        token![/] => Ok((left / right).into()),
+        token![%] => Ok((left % right).into()),
+        token![**] => big_integer_power(left, right),
+        _ => Err(invalid_operator("integer", &left, operator, &right)),
+    }
+}
+
+// Powers of integers with more bits than this are refused instead of running out of memory
+const MAX_POWER_BITS: u64 = 1 << 24;
+
+fn big_integer_power(left: BigInt, right: BigInt) -> Result<Value, EvaluatorError> {
+    if right.is_negative() {
+        let (left, right) = (to_float(&left.into()), to_float(&right.into()));
+        return float_infix(left, &token![**], right);
+    }
+
+    // 0, 1 and -1 stay small no matter how large the exponent is
+    if left.magnitude().is_one() || left.is_zero() {
+        let odd = (&right % 2u32).is_one();
+        return Ok(match (left.is_negative(), odd, right.is_zero()) {
+            (_, _, true) => Value::Integer(1),
+            (true, false, _) => Value::Integer(1),
+            _ => left.into(),
+        });
+    }
+
+    let bits = right
+        .to_u64()
+        .map_or(u64::MAX, |exp| left.bits().saturating_mul(exp));
+
+    match right.to_u32() {
+        Some(exp) if bits <= MAX_POWER_BITS => Ok(left.pow(exp).into()),
+        _ => Err(EvaluatorError::new(
+            EvaluatorErrorKind::LimitExceeded,
+            format!("Integer too large: ({left} ** {right})"),
+        )
+        .with_note(format!("the result would have more than {MAX_POWER_BITS} bits"))),
+    }
+}
+
+// Follows IEEE 754, so dividing by zero gives infinity or NaN instead of an error
+fn float_infix(left: f64, operator: &Token, right: f64) -> Result<Value, EvaluatorError> {
+    match operator {
+        token![+] => Ok((left + right).into()),
+        token![-] => Ok((left - right).into()),
+        token![*] => Ok((left * right).into()),
+        token![/] => Ok((left / right).into()),
+        token![%] => Ok((left % right).into()),
+        token![**] => Ok(left.powf(right).into()),
+        _ => Err(invalid_operator("float", &Value::Float(left), operator, &Value::Float(right))),
+    }
+}
+
+fn invalid_operator(
+    kind: &str,
+    left: &dyn Display,
+    operator: &Token,
+    right: &dyn Display,
+) -> EvaluatorError {
+    EvaluatorError::new(
+        EvaluatorErrorKind::TypeMismatch,
+        format!("Invalid {kind} infix operator: ({left} {operator} {right})"),
+    )
+    .with_note(format!("{operator} is not a valid {kind} operator"))
+}
+
+// Every pair of values can be checked for equality, values of different types are never
+// equal except for numbers, which are compared by their value (1 == 1.0)
+pub fn equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Integer(l), Value::Integer(r)) => l == r,
+        (Value::Integer(_) | Value::BigInt(_), Value::Integer(_) | Value::BigInt(_)) => {
+            to_big_integer(left) == to_big_integer(right)
+        }
+        (Value::Float(_), Value::Integer(_) | Value::BigInt(_) | Value::Float(_))
+        | (Value::Integer(_) | Value::BigInt(_), Value::Float(_)) => {
+            to_float(left) == to_float(right)
+        }
+        (Value::Boolean(l), Value::Boolean(r)) => l == r,
+        (Value::String(l), Value::String(r)) => l == r,
+        (Value::Null, Value::Null) => true,
+        (Value::Array(l), Value::Array(r)) => {
+            l.len() == r.len() && l.iter().zip(r).all(|(l, r)| equals(l, r))
+        }
+        (Value::Hash(l), Value::Hash(r)) => {
+            l.len() == r.len()
+                && l.iter()
+                    .zip(r)
+                    .all(|((lk, lv), (rk, rv))| lk == rk && equals(lv, rv))
+        }
+        (Value::Range(l), Value::Range(r)) => l == r,
+        // Functions are equal when they come from the same function literal and were
+        // created in the same scope
+        (
+            Value::Function {
+                params: lp,
+                body: lb,
+                env: le,
+            },
+            Value::Function {
+                params: rp,
+                body: rb,
+                env: re,
+            },
+        ) => lp == rp && lb == rb && Rc::ptr_eq(le, re),
+        (Value::BuiltInFunction { func: l }, Value::BuiltInFunction { func: r }) => l == r,
+        (Value::NativeFunction(l), Value::NativeFunction(r)) => Rc::ptr_eq(l, r),
+        (Value::Closure(l), Value::Closure(r)) => {
+            let same_env = match (&l.env, &r.env) {
+                (Some(l), Some(r)) => Rc::ptr_eq(l, r),
+                (l, r) => l.is_none() && r.is_none(),
+            };
+
+            Rc::ptr_eq(&l.func, &r.func) && same_env
+        }
+        (Value::Iterator(l), Value::Iterator(r)) => Rc::ptr_eq(l, r),
+        // Modules are cached so importing the same file twice gives the same module
+        (Value::Module(l), Value::Module(r)) => Rc::ptr_eq(l, r),
+        (Value::Return(l), Value::Return(r)) => equals(l, r),
+        (Value::Break, Value::Break) | (Value::Continue, Value::Continue) => true,
+        _ => false,
+    }
+}
+
+// Numbers are ordered by value and strings lexicographically, by their characters
+fn compare(left: &Value, operator: &Token, right: &Value) -> Result<Value, EvaluatorError> {
+    let ordering = match (left, right) {
+        (Value::Integer(l), Value::Integer(r)) => l.partial_cmp(r),
+        (Value::Integer(_) | Value::BigInt(_), Value::Integer(_) | Value::BigInt(_)) => {
+            to_big_integer(left).partial_cmp(&to_big_integer(right))
+        }
+        (Value::Float(_), Value::Integer(_) | Value::BigInt(_) | Value::Float(_))
+        | (Value::Integer(_) | Value::BigInt(_), Value::Float(_)) => {
+            to_float(left).partial_cmp(&to_float(right))
+        }
+        (Value::String(l), Value::String(r)) => l.partial_cmp(r),
+        _ => {
+            return Err(EvaluatorError::new(
+                EvaluatorErrorKind::TypeMismatch,
+                format!("Cannot compare {left} and {right} with {operator}"),
+            )
+            .with_note("only numbers and strings can be ordered"))
+        }
+    };
+
+    // NaN is unordered, so every comparison with it is false
+    let result = match operator {
+        token![<] => ordering == Some(Ordering::Less),
+        token![>] => ordering == Some(Ordering::Greater),
+        token![<=] => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        _ => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+    };
+
+    Ok(result.into())
+}
+
+fn division_by_zero(expression: String) -> EvaluatorError {
+    EvaluatorError::new(
+        EvaluatorErrorKind::DivisionByZero,
+        format!("Division by zero: {expression}"),
+    )
+}
+
+fn to_big_integer(value: &Value) -> BigInt {
+    match value {
+        Value::Integer(i) => BigInt::from(*i),
+        Value::BigInt(i) => i.clone(),
+        _ => unreachable!("only integers are converted to big integers"),
+    }
+}
+
+pub fn to_float(value: &Value) -> f64 {
+    match value {
+        Value::Integer(i) => *i as f64,
+        Value::BigInt(i) => big_integer_to_float(i),
+        Value::Float(f) => *f,
+        _ => unreachable!("only numbers are converted to floats"),
+    }
+}
+
+// Big integers too large for a float become infinity with the same sign
+pub fn big_integer_to_float(i: &BigInt) -> f64 {
+    i.to_f64().unwrap_or(match i.is_negative() {
+        true => f64::NEG_INFINITY,
+        false => f64::INFINITY,
+    })
+}
+
+pub fn index(left: Value, index: Value) -> Result<Value, EvaluatorError> {
+    match (&left, &index) {
+        (Value::Array(elements), Value::Integer(i)) => {
+            // Out of bounds (including negative) indexes evaluate to null
+            Ok(usize::try_from(*i)
+                .ok()
+                .and_then(|i| elements.get(i))
+                .cloned()
+                .unwrap_or(Value::Null))
+        }
+        // Strings are indexed by character, not by byte
+        (Value::String(s), Value::Integer(i)) => Ok(usize::try_from(*i)
+            .ok()
+            .and_then(|i| s.chars().nth(i))
+            .map_or(Value::Null, Value::from)),
+        // Far out of bounds
+        (Value::Array(_) | Value::String(_), Value::BigInt(_)) => Ok(Value::Null),
+        (Value::Hash(pairs), _) => match index.hash_key() {
+            Some(key) => Ok(pairs.get(&key).cloned().unwrap_or(Value::Null)),
+            None => Err(unusable_hash_key(&index)),
+        },
+        _ => Err(EvaluatorError::new(
+            EvaluatorErrorKind::TypeMismatch,
+            format!("Index operator not supported: ({left}[{index}])"),
+        )),
+    }
+}
+
+// `module.name`, only modules have members
+pub fn member(left: Value, name: &str) -> Result<Value, EvaluatorError> {
+    match &left {
+        Value::Module(module) => module.bindings.get(name).cloned().ok_or_else(|| {
+            EvaluatorError::new(
+                EvaluatorErrorKind::IdentifierNotFound(name.to_string()),
+                format!("module {:?} has no binding named {name}", module.path),
+            )
+        }),
+        _ => Err(EvaluatorError::new(
+            EvaluatorErrorKind::TypeMismatch,
+            format!("Member access not supported: ({left}.{name}), {left} is not a module"),
+        )),
+    }
+}
+
+pub fn unusable_hash_key(key: &Value) -> EvaluatorError {
+    EvaluatorError::new(
+        EvaluatorErrorKind::UnusableHashKey,
+        format!("Unusable as hash key: {key}"),
+    )
+}
+
+// The values a for loop visits, strings are iterated one character at a time
+pub fn iterate(value: Value) -> Result<ValueIter, EvaluatorError> {
+    match value {
+        Value::Array(elements) => Ok(ValueIter::new(elements.into_iter())),
+        Value::String(s) => {
+            let chars = s.chars().map(Value::from).collect::<Vec<Value>>();
+            Ok(ValueIter::new(chars.into_iter()))
+        }
+        Value::Range(range) => Ok(ValueIter::new(range.iter().map(Value::Integer))),
+        _ => Err(EvaluatorError::new(
+            EvaluatorErrorKind::TypeMismatch,
+            format!("Cannot iterate over {value}"),
+        )),
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+// The pseudo random number generator behind the random builtins (xoshiro256**)
+//
+// Each interpreter owns one so that a seeded program always produces the same numbers.
+// Without a seed it is seeded from the OS the first time a number is needed
+#[derive(Debug, Clone, Default)]
+pub struct Random {
+    state: Option<[u64; 4]>,
+}
+
+impl Random {
+    // Expands the seed into the full state with splitmix64, which never produces the all
+    // zero state that xoshiro cannot leave
+    pub fn seed(&mut self, seed: u64) {
+        let mut seed = seed;
+        let mut next = || {
+            seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        };
+
+        self.state = Some([next(), next(), next(), next()]);
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = self.state.get_or_insert_with(|| {
+            // The hasher's keys are random for every process
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u8(0);
+            let mut random = Random::default();
+            random.seed(hasher.finish());
+            random.state.unwrap()
+        });
+
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+
+        result
+    }
+
+    // A float in [0, 1) with all 53 bits of precision
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // A number in [0, bound) without the bias of a plain modulo, a bound of 0 means every u64
+    pub fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            return self.next_u64();
+        }
+
+        // Reject the numbers at the top that would make the low results more likely
+        let zone = u64::MAX - (u64::MAX - bound + 1) % bound;
+        loop {
+            let x = self.next_u64();
+            if x <= zone {
+                return x % bound;
+            }
+        }
+    }
+}
@@ -1,21 +1,71 @@
+use std::{cell::RefCell, collections::HashMap, mem::discriminant, rc::Rc};
+
 use crate::{
+    compiler::Compiler,
+    error::{Error, EvaluatorError, EvaluatorErrorKind},
     lexer::Lexer,
-    parser::{ast::Ast, Parser},
+    parser::{ast::Ast, program::Program, Parser},
+    vm::Vm,
+    Backend, Monkey,
 };
 
-use super::{value::Value, Evaluator};
+use super::{
+    convert::{FromValue, IntoValue},
+    ops,
+    value::{HashKey, Value},
+    Evaluator,
+};
 
-fn test_eval(input: &str) -> Value {
+fn parse(input: &str) -> Program {
     let lexer = Lexer::new(input);
     let mut parser = Parser::new(lexer);
 
     let program = parser.parse_program();
     assert!(parser.errors.is_empty(), "Parser had errors");
 
-    let result = Evaluator::eval(&Ast::Program(program));
-    match result {
-        Ok(v) => v,
-        Err(e) => panic!("Error: {}", e),
+    program
+}
+
+// Every program is run on both the evaluator and the vm, which have to agree on the result
+fn eval_both(input: &str) -> (Result<Value, EvaluatorError>, Result<Value, EvaluatorError>) {
+    let evaluated = Evaluator::eval(&Ast::Program(parse(input)));
+
+    let vm_evaluated = Compiler::new()
+        .compile(&parse(input))
+        .and_then(|bytecode| Vm::new().run(&bytecode));
+
+    (evaluated, vm_evaluated)
+}
+
+fn test_eval(input: &str) -> Value {
+    match eval_both(input) {
+        (Ok(v), Ok(vm_v)) => {
+            let is_function = |v: &Value| matches!(v, Value::Function { .. } | Value::Closure(_));
+
+            assert!(
+                discriminant(&v) == discriminant(&vm_v) || (is_function(&v) && is_function(&vm_v)),
+                "backends returned different types for {}. evaluator: {:?}, vm: {:?}",
+                input,
+                v,
+                vm_v
+            );
+            assert_eq!(v.to_string(), vm_v.to_string(), "backends disagree on {}", input);
+
+            v
+        }
+        (Err(e), _) => panic!("Error: {}", e),
+        (_, Err(e)) => panic!("Vm error: {}", e),
+    }
+}
+
+fn test_eval_error(input: &str) -> EvaluatorError {
+    match eval_both(input) {
+        (Err(e), Err(vm_e)) => {
+            assert_eq!(e, vm_e, "backends returned different errors for {}", input);
+            e
+        }
+        (Ok(v), _) => panic!("no error returned. got {:?}", v),
+        (_, Ok(v)) => panic!("no error returned by the vm. got {:?}", v),
     }
 }
 
@@ -31,6 +81,8 @@ fn test_eval_integer_expression() {
         ("2*2*2*2*2", 32),
         ("3 * (3 *3)+10", 37),
         ("(5+10*2+15/3)*2+-10", 50),
+        ("1_000_000 + 0xFF - 0o17 * 0b10", 1_000_225),
+        ("let player1 = 2; let player2 = 3; player1 * player2", 6),
     ];
 
     for (input, expected) in tests {
@@ -182,7 +234,7 @@ fn test_functions() {
                 body.to_string(),
                 "(x + 2)",
                 "body is not '(x + 2)'. got {}",
-                body.to_string()
+                body
             );
         }
         _ => panic!("evaluated value is not a Value::Function"),
@@ -206,6 +258,37 @@ fn test_function_appliction() {
     }
 }
 
+#[test]
+fn test_names_resolved_at_runtime() {
+    let tests = vec![
+        // Bindings defined after the function using them
+        ("let outer = fn() { let f = fn() { g() }; let g = fn() { 2 }; f() }; outer()", 2),
+        ("let f = fn() { len([1]) }; let len = fn(x) { 99 }; f()", 99),
+        ("let x = 1; let g = fn() { let h = fn() { x }; let x = 2; h() }; g()", 2),
+        // Until the binding is defined the outer one (or the builtin) is used
+        ("let x = 1; let g = fn() { let h = fn() { x }; let y = h(); let x = 2; y }; g()", 1),
+        ("let f = fn() { len([1, 2]) }; let a = f(); let len = fn(x) { 99 }; a + f()", 101),
+    ];
+
+    for (input, expected) in tests {
+        test_integer_object(test_eval(input), expected);
+    }
+
+    let error = test_eval_error("let f = fn() { g() }; f(); let g = fn() { 1 };");
+    assert_eq!(error.kind, EvaluatorErrorKind::IdentifierNotFound("g".to_string()));
+    assert_eq!(error.span.unwrap().to_string(), "1:16");
+
+    // Also when the binding is defined by a later program, like the next line in the REPL
+    for backend in [Backend::Evaluator, Backend::Vm] {
+        let mut monkey = Monkey::new(backend);
+        monkey.run("let f = fn() { len([1]) }; let a = f();").unwrap();
+        monkey.run("let len = fn(x) { 99 };").unwrap();
+
+        let result = monkey.run("a + f()").unwrap();
+        assert_eq!(result.to_string(), "100", "backend: {backend:?}");
+    }
+}
+
 #[test]
 fn test_strings() {
     let input = r#"
@@ -224,15 +307,1394 @@ fn test_strings() {
     );
 }
 
+#[test]
+fn test_interpolated_strings() {
+    let tests = vec![
+        (r#"let x = 3; f"Total: {x} items""#, "Total: 3 items"),
+        (r#"f"{1 + 2.5} {true} {[1, "a"]} {{}}""#, "3.5 true [1, a] {}"),
+        (r#"let name = "monkey"; f"Hello, {name + "!"}""#, "Hello, monkey!"),
+        (r#"let f = fn(n) { f"<{n}>" }; f"{f(f(1))}""#, "<<1>>"),
+        (r#"f"{len("abc")}\t{if (false) { 1 }}""#, "3\tnull"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+
+        assert_eq!(evaluated.to_string(), expected, "input: {}", input);
+    }
+}
+
 #[test]
 fn test_built_in_funcs() {
     let tests = vec![
         ("len(\"\")", 0),
-        ("len(\"five\")", 4)
+        ("len(\"five\")", 4),
+        // Characters, not bytes
+        ("len(\"héllo\")", 5),
+        ("len(\"日本語🐒\")", 4),
     ];
 
     for (input, expected) in tests {
         let evaluated = test_eval(input);
         test_integer_object(evaluated, expected);
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_unicode_strings() {
+    let tests = vec![
+        (r#""日本語"[1]"#, "本"),
+        (r#""héllo"[4]"#, "o"),
+        (r#""héllo"[5]"#, "null"),
+        (r#""héllo"[-1]"#, "null"),
+        (r#"first("éa")"#, "é"),
+        (r#"last("a🐒")"#, "🐒"),
+        (r#"rest("éab")"#, "ab"),
+        (r#"first("")"#, "null"),
+        (r#"let größe = 3; let 変数 = "猿"; f"{größe}{変数}""#, "3猿"),
+        (r#"let n = 0; for (c in "🐒é") { n += len(c); } n"#, "2"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+
+        assert_eq!(evaluated.to_string(), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_string_builtins() {
+    let tests = vec![
+        (r#"split("a,b,,c", ",")"#, "[a, b, , c]"),
+        (r#"split("  one two\tthree ")"#, "[one, two, three]"),
+        (r#"join(["a", 1, true], ", ")"#, "a, 1, true"),
+        (r#"join(split("a b", " "))"#, "ab"),
+        (r#"trim("  hi \n")"#, "hi"),
+        (r#"upper("straße")"#, "STRASSE"),
+        (r#"lower("HeLLo")"#, "hello"),
+        (r#"replace("a-b-c", "-", "+")"#, "a+b+c"),
+        (r#"contains("monkey", "key")"#, "true"),
+        (r#"contains([1, "a", [2]], [2])"#, "true"),
+        (r#"contains([1, 2], 3)"#, "false"),
+        (r#"starts_with("monkey", "mon")"#, "true"),
+        (r#"ends_with("monkey", "mon")"#, "false"),
+        (r#"index_of("héllo", "l")"#, "2"),
+        (r#"index_of([1, 2.0, 3], 2)"#, "1"),
+        (r#"index_of("abc", "z")"#, "null"),
+        (r#"substr("héllo", 1, 3)"#, "éll"),
+        (r#"substr("héllo", 3)"#, "lo"),
+        (r#"substr("abc", 5, 1)"#, ""),
+        (r#"slice("héllo", 1, -1)"#, "éll"),
+        (r#"slice("héllo", -2)"#, "lo"),
+        (r#"slice([1, 2, 3, 4], 1, 3)"#, "[2, 3]"),
+        (r#"slice([1, 2, 3], 2, 1)"#, "[]"),
+        (r#"slice([1, 2, 3], -10, 10)"#, "[1, 2, 3]"),
+        (r#"repeat("ab", 3)"#, "ababab"),
+        (r#"repeat("ab", 0)"#, ""),
+        (r#"chars("日本")"#, "[日, 本]"),
+        (r#"format("{} + {} = {}", 1, 2.5, 3.5)"#, "1 + 2.5 = 3.5"),
+        (r#"format("{{{}}}", [1])"#, "{[1]}"),
+        (r#"format("plain")"#, "plain"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+
+        assert_eq!(evaluated.to_string(), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_string_builtin_errors() {
+    let tests = vec![
+        (
+            r#"split("a b", " ", 1)"#,
+            EvaluatorErrorKind::WrongArgumentRange { min: 1, max: 2, given: 3 },
+            "split() takes 1 to 2 arguments, 3 arguments given",
+        ),
+        (
+            r#"substr("abc")"#,
+            EvaluatorErrorKind::WrongArgumentRange { min: 2, max: 3, given: 1 },
+            "substr() takes 2 to 3 arguments, 1 arguments given",
+        ),
+        (
+            r#"split("a b", "")"#,
+            EvaluatorErrorKind::InvalidArgument,
+            "split() separator cannot be empty",
+        ),
+        (
+            r#"join("abc", ",")"#,
+            EvaluatorErrorKind::InvalidArgument,
+            "join() expects argument 1 to be an array, got abc",
+        ),
+        (
+            "upper(5)",
+            EvaluatorErrorKind::InvalidArgument,
+            "upper() expects argument 1 to be a string, got 5",
+        ),
+        (
+            r#"replace("a", "b")"#,
+            EvaluatorErrorKind::WrongArgumentCount { expected: 3, given: 2 },
+            "replace() takes 3 arguments, 2 arguments given",
+        ),
+        (
+            r#"contains(5, "a")"#,
+            EvaluatorErrorKind::InvalidArgument,
+            "contains() expects argument 1 to be a string or an array, got 5",
+        ),
+        (
+            r#"index_of("abc", 1)"#,
+            EvaluatorErrorKind::InvalidArgument,
+            "index_of() expects argument 2 to be a string, got 1",
+        ),
+        (
+            r#"substr("abc", -1)"#,
+            EvaluatorErrorKind::InvalidArgument,
+            "substr() start cannot be negative, got -1",
+        ),
+        (
+            r#"slice("abc", "a")"#,
+            EvaluatorErrorKind::InvalidArgument,
+            "slice() expects argument 2 to be an integer, got a",
+        ),
+        (
+            r#"repeat("a", -1)"#,
+            EvaluatorErrorKind::InvalidArgument,
+            "repeat() count cannot be negative, got -1",
+        ),
+        (
+            r#"repeat("ab", 9223372036854775807)"#,
+            EvaluatorErrorKind::LimitExceeded,
+            "String too large: repeat() of 2 bytes 9223372036854775807 times",
+        ),
+        (
+            "format()",
+            EvaluatorErrorKind::WrongArgumentCount { expected: 1, given: 0 },
+            "format() takes 1 argument, 0 arguments given",
+        ),
+        (
+            r#"format("{} {}", 1)"#,
+            EvaluatorErrorKind::InvalidArgument,
+            "format() has 2 placeholders but 1 values were given",
+        ),
+        (
+            r#"format("{x}", 1)"#,
+            EvaluatorErrorKind::InvalidArgument,
+            "format() found an unmatched '{' in \"{x}\"",
+        ),
+    ];
+
+    for (input, expected_kind, expected_message) in tests {
+        let error = test_eval_error(input);
+
+        assert_eq!(error.kind, expected_kind, "input: {}", input);
+        assert_eq!(error.message, expected_message, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_math() {
+    let tests = vec![
+        ("2 ** 10", "1024"),
+        ("2 ** 3 ** 2", "512"),
+        ("-2 ** 2", "-4"),
+        ("(-2) ** 3", "-8"),
+        ("2 ** -1", "0.5"),
+        ("4.0 ** 0.5", "2.0"),
+        ("2 ** 64", "18446744073709551616"),
+        ("(2 ** 64) ** 2 / 2 ** 127", "2"),
+        ("0 ** 0", "1"),
+        ("(-1) ** (10 ** 30 + 1)", "-1"),
+        ("let x = 3; x ** 2 * 2", "18"),
+        ("abs(-5)", "5"),
+        ("abs(-9223372036854775807 - 1)", "9223372036854775808"),
+        ("abs(-2.5)", "2.5"),
+        ("min(3, 1, 2)", "1"),
+        ("max([1, 5.5, 2])", "5.5"),
+        (r#"min("b", "a", "c")"#, "a"),
+        ("pow(2, 8)", "256"),
+        ("sqrt(16)", "4.0"),
+        ("log(8, 2)", "3.0"),
+        ("exp(0)", "1.0"),
+        ("sin(0)", "0.0"),
+        ("cos(PI)", "-1.0"),
+        ("atan2(0, -1) == PI", "true"),
+        ("TAU == 2 * PI", "true"),
+        ("floor(E * 100)", "271"),
+        ("gcd(12, -18)", "6"),
+        ("gcd(0, 0)", "0"),
+        ("gcd(2 ** 70, 6)", "2"),
+        ("lcm(4, 6)", "12"),
+        ("lcm(0, 6)", "0"),
+        // Builtins and constants can be shadowed
+        ("let PI = 3; let abs = fn(x) { x }; abs(-PI)", "-3"),
+        ("let f = fn() { PI }; let PI = 3; f()", "3"),
+        ("let f = fn() { E }; let e = f(); let E = 1; [e, f()]", "[2.718281828459045, 1]"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+
+        assert_eq!(evaluated.to_string(), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_math_constants_in_later_programs() {
+    for backend in [Backend::Evaluator, Backend::Vm] {
+        let mut monkey = Monkey::new(backend);
+
+        let mut run = |input| monkey.run(input).unwrap().to_string();
+        assert_eq!(run("PI == TAU / 2"), "true", "backend: {backend:?}");
+        assert_eq!(run("let f = fn() { floor(E) }; PI > 3"), "true", "backend: {backend:?}");
+        assert_eq!(run("[f(), floor(TAU)]"), "[2, 6]", "backend: {backend:?}");
+        assert_eq!(run("let E = 1; f()"), "1", "backend: {backend:?}");
+        assert_eq!(run("floor(PI)"), "3", "backend: {backend:?}");
+    }
+}
+
+#[test]
+fn test_math_errors() {
+    let tests = vec![
+        (
+            r#"abs("a")"#,
+            EvaluatorErrorKind::InvalidArgument,
+            "abs() expects argument 1 to be a number, got a",
+        ),
+        (
+            "min()",
+            EvaluatorErrorKind::WrongArgumentCount { expected: 1, given: 0 },
+            "min() takes 1 argument, 0 arguments given",
+        ),
+        ("max([])", EvaluatorErrorKind::InvalidArgument, "max() of an empty array"),
+        (r#"min(1, "a")"#, EvaluatorErrorKind::TypeMismatch, "Cannot compare a and 1 with <"),
+        (
+            "pow(true, 2)",
+            EvaluatorErrorKind::InvalidArgument,
+            "pow() expects argument 1 to be a number, got true",
+        ),
+        (
+            "gcd(1.5, 2)",
+            EvaluatorErrorKind::InvalidArgument,
+            "gcd() expects argument 1 to be an integer, got 1.5",
+        ),
+        ("3 ** 100000000", EvaluatorErrorKind::LimitExceeded, "Integer too large: (3 ** 100000000)"),
+        (r#""a" ** 2"#, EvaluatorErrorKind::TypeMismatch, "Invalid infix expression: (a ** 2)"),
+    ];
+
+    for (input, expected_kind, expected_message) in tests {
+        let error = test_eval_error(input);
+
+        assert_eq!(error.kind, expected_kind, "input: {}", input);
+        assert_eq!(error.message, expected_message, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_random() {
+    let tests = vec![
+        (
+            r#"let f = fn() { [random(), random_int(1, 6), shuffle([1, 2, 3]), choice(["x", "y"])] };
+            seed(1); let a = f(); seed(1); a == f()"#,
+            "true",
+        ),
+        ("seed(1); let a = random(); seed(2); a == random()", "false"),
+        ("seed(3); let x = random(); x >= 0 && x < 1", "true"),
+        (
+            "seed(4); let seen = [];
+            for (i in range(100)) {
+                let x = random_int(-2, 2);
+                if (x >= -2 && x <= 2 && !contains(seen, x)) { seen = push(seen, x); }
+            }
+            len(seen)",
+            "5",
+        ),
+        (
+            "seed(5); let x = random_int(-9223372036854775807 - 1, 9223372036854775807); x == x",
+            "true",
+        ),
+        ("seed(6); random_int(7, 7)", "7"),
+        (
+            "seed(7); let s = shuffle([1, 2, 3, 4, 5]); len(s) == 5 && contains(s, 1) && contains(s, 5)",
+            "true",
+        ),
+        ("seed(8); contains([1, 2, 3], choice([1, 2, 3]))", "true"),
+        ("shuffle([])", "[]"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+
+        assert_eq!(evaluated.to_string(), expected, "input: {}", input);
+    }
+
+    // Seeding the interpreter is the same as calling seed() first
+    let input = "[random(), random_int(0, 1000000), shuffle([1, 2, 3, 4, 5, 6])]";
+
+    let mut evaluator = Evaluator::new();
+    evaluator.seed(99);
+    let evaluated = evaluator.eval_self(&Ast::Program(parse(input))).unwrap();
+
+    let mut vm = Vm::new();
+    vm.seed(99);
+    let vm_evaluated = vm.run(&Compiler::new().compile(&parse(input)).unwrap()).unwrap();
+
+    let seeded = test_eval(&format!("seed(99); {input}"));
+    assert_eq!(evaluated.to_string(), vm_evaluated.to_string());
+    assert_eq!(evaluated.to_string(), seeded.to_string());
+}
+
+#[test]
+fn test_random_errors() {
+    let tests = vec![
+        (
+            "random(1)",
+            EvaluatorErrorKind::WrongArgumentCount { expected: 0, given: 1 },
+            "random() takes 0 arguments, 1 arguments given",
+        ),
+        (
+            "random_int(5, 1)",
+            EvaluatorErrorKind::InvalidArgument,
+            "random_int() low cannot be larger than high, got 5 and 1",
+        ),
+        ("choice([])", EvaluatorErrorKind::InvalidArgument, "choice() of an empty array"),
+        (
+            "shuffle(5)",
+            EvaluatorErrorKind::InvalidArgument,
+            "shuffle() expects argument 1 to be an array, got 5",
+        ),
+        (
+            "seed(1.5)",
+            EvaluatorErrorKind::InvalidArgument,
+            "seed() expects argument 1 to be an integer, got 1.5",
+        ),
+    ];
+
+    for (input, expected_kind, expected_message) in tests {
+        let error = test_eval_error(input);
+
+        assert_eq!(error.kind, expected_kind, "input: {}", input);
+        assert_eq!(error.message, expected_message, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_array_literals() {
+    let evaluated = test_eval("[1, 2 * 2, 3 + 3]");
+
+    match evaluated {
+        Value::Array(elements) => {
+            assert_eq!(elements.len(), 3, "array has wrong number of elements. got {}", elements.len());
+
+            test_integer_object(elements[0].clone(), 1);
+            test_integer_object(elements[1].clone(), 4);
+            test_integer_object(elements[2].clone(), 6);
+        }
+        _ => panic!("value is not an Array. got {:?}", evaluated),
+    }
+}
+
+#[test]
+fn test_array_index_expressions() {
+    let tests = vec![
+        ("[1, 2, 3][0]", Some(1)),
+        ("[1, 2, 3][1]", Some(2)),
+        ("[1, 2, 3][2]", Some(3)),
+        ("let i = 0; [1][i];", Some(1)),
+        ("[1, 2, 3][1 + 1];", Some(3)),
+        ("let myArray = [1, 2, 3]; myArray[2];", Some(3)),
+        ("let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2];", Some(6)),
+        ("[1, 2, 3][3]", None),
+        ("[1, 2, 3][-1]", None),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+
+        match expected {
+            Some(i) => test_integer_object(evaluated, i),
+            None => assert!(
+                evaluated.is_null(),
+                "value is not Null. got {:?}",
+                evaluated
+            ),
+        }
+    }
+}
+
+#[test]
+fn test_array_built_in_funcs() {
+    let tests = vec![
+        ("len([1, 2, 3])", "3"),
+        ("len([])", "0"),
+        ("first([1, 2, 3])", "1"),
+        ("first([])", "null"),
+        ("last([1, 2, 3])", "3"),
+        ("last([])", "null"),
+        ("rest([1, 2, 3])", "[2, 3]"),
+        ("rest([])", "null"),
+        ("push([], 1)", "[1]"),
+        ("let a = [1]; push(a, 2); a", "[1]"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.to_string(), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_hash_literals() {
+    let input = r#"
+        let two = "two";
+        {
+            "one": 10 - 9,
+            two: 1 + 1,
+            "thr" + "ee": 6 / 2,
+            4: 4,
+            true: 5,
+            false: 6
+        }
+    "#;
+
+    let evaluated = test_eval(input);
+
+    match evaluated {
+        Value::Hash(pairs) => {
+            let expected = vec![
+                (HashKey::String("one".to_string()), 1),
+                (HashKey::String("two".to_string()), 2),
+                (HashKey::String("three".to_string()), 3),
+                (HashKey::Integer(4), 4),
+                (HashKey::Boolean(true), 5),
+                (HashKey::Boolean(false), 6),
+            ];
+
+            assert_eq!(pairs.len(), expected.len(), "hash has wrong number of pairs. got {}", pairs.len());
+
+            for (key, value) in expected {
+                let pair = pairs.get(&key);
+                assert!(pair.is_some(), "no pair for given key {} in pairs", key);
+                test_integer_object(pair.unwrap().clone(), value);
+            }
+        }
+        _ => panic!("value is not a Hash. got {:?}", evaluated),
+    }
+}
+
+#[test]
+fn test_hash_index_expressions() {
+    let tests = vec![
+        (r#"{"foo": 5}["foo"]"#, Some(5)),
+        (r#"{"foo": 5}["bar"]"#, None),
+        (r#"let key = "foo"; {"foo": 5}[key]"#, Some(5)),
+        (r#"{}["foo"]"#, None),
+        ("{5: 5}[5]", Some(5)),
+        ("{true: 5}[true]", Some(5)),
+        ("{false: 5}[false]", Some(5)),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+
+        match expected {
+            Some(i) => test_integer_object(evaluated, i),
+            None => assert!(
+                evaluated.is_null(),
+                "value is not Null. got {:?}",
+                evaluated
+            ),
+        }
+    }
+}
+
+#[test]
+fn test_hash_built_in_funcs() {
+    let tests = vec![
+        (r#"keys({"b": 2, "a": 1})"#, "[a, b]"),
+        (r#"values({"b": 2, "a": 1})"#, "[1, 2]"),
+        (r#"has_key({"a": 1}, "a")"#, "true"),
+        (r#"has_key({"a": 1}, "b")"#, "false"),
+        (r#"delete({"a": 1, "b": 2}, "a")"#, "{b: 2}"),
+        (r#"let h = {"a": 1}; delete(h, "a"); h"#, "{a: 1}"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.to_string(), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_error_handling() {
+    let tests = vec![
+        (
+            "5 + true;",
+            EvaluatorErrorKind::TypeMismatch,
+            "Invalid infix expression: (5 + true)",
+        ),
+        (
+            "-true",
+            EvaluatorErrorKind::TypeMismatch,
+            "Invalid prefix expression: (-true)",
+        ),
+        (
+            "if 10 > 1 { true + false; }",
+            EvaluatorErrorKind::TypeMismatch,
+            "Invalid infix expression: (true + false)",
+        ),
+        (
+            "foobar",
+            EvaluatorErrorKind::IdentifierNotFound("foobar".to_string()),
+            "identifier not found: foobar",
+        ),
+        (
+            r#"{"name": "Monkey"}[fn(x) { x }];"#,
+            EvaluatorErrorKind::UnusableHashKey,
+            "Unusable as hash key: fn(x) {\nx\n}",
+        ),
+        (
+            "len(1)",
+            EvaluatorErrorKind::InvalidArgument,
+            "Cannot take length of 1",
+        ),
+        (
+            r#"len("one", "two")"#,
+            EvaluatorErrorKind::WrongArgumentCount { expected: 1, given: 2 },
+            "len() takes 1 argument, 2 arguments given",
+        ),
+        (
+            "fn(x, y) { x + y }(1)",
+            EvaluatorErrorKind::WrongArgumentCount { expected: 2, given: 1 },
+            "fn(x, y) takes 2 arguments, 1 arguments given",
+        ),
+        (
+            "5()",
+            EvaluatorErrorKind::NotCallable,
+            "Cannot call 5, it is not a function",
+        ),
+    ];
+
+    for (input, expected_kind, expected_message) in tests {
+        let error = test_eval_error(input);
+
+        assert_eq!(error.kind, expected_kind, "input: {}", input);
+        assert_eq!(error.message, expected_message, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_error_location() {
+    let input = "let add = fn(x, y) {\n    x + y\n};\nadd(1, true);";
+
+    let error = test_eval_error(input);
+
+    // The error points at the infix expression inside the function body
+    assert_eq!(error.span.map(|span| (span.line, span.column)), Some((2, 5)));
+    assert_eq!(error.to_string(), "2:5: Invalid infix expression: (1 + true)");
+}
+
+#[test]
+fn test_while_loops() {
+    let tests = vec![
+        ("let i = 0; while (i < 10) { let i = i + 1; }; i", 10),
+        ("let i = 0; while (true) { let i = i + 1; if (i == 5) { break; } }; i", 5),
+        (
+            "let i = 0; let sum = 0; while (i < 10) { let i = i + 1; if (i % 2 == 0) { continue; } let sum = sum + i; }; sum",
+            25,
+        ),
+        (
+            "let i = 0; let n = 0; while (i < 3) { let i = i + 1; let j = 0; while (true) { let j = j + 1; let n = n + 1; if (j == 4) { break; } } }; n",
+            12,
+        ),
+        (
+            "let f = fn() { let i = 0; while (true) { let i = i + 1; if (i == 7) { return i; } } }; f()",
+            7,
+        ),
+    ];
+
+    for (input, expected) in tests {
+        test_integer_object(test_eval(input), expected);
+    }
+
+    // Loops do not produce a value
+    assert!(test_eval("5; while (false) { 1 }").is_null());
+    assert!(test_eval("fn() { while (false) { 1 } }()").is_null());
+}
+
+#[test]
+fn test_for_loops() {
+    let tests = vec![
+        ("let xs = []; for (x in [1, 2, 3]) { let xs = push(xs, x * 2); xs }", "null"),
+        ("let f = fn(xs) { let sum = 0; for (x in xs) { let sum = sum + x; }; sum }; f([1, 2])", "0"),
+        ("let f = fn(s) { for (c in s) { return c + \"!\"; } }; f(\"hello\")", "h!"),
+        ("let last = fn(xs) { let result = 0; for (x in xs) { return x; } }; last(range(3, 10))", "3"),
+        (
+            "let out = fn(r) { for (i in r) { if (i == 4) { break; } if (i % 2 == 0) { continue; } return i; } }; out(range(10))",
+            "1",
+        ),
+        ("let out = fn(r) { for (i in r) { if (i == 2) { break; } }; 7 }; out(range(10))", "7"),
+        (
+            "let out = fn() { for (i in range(3)) { for (j in range(3)) { if (j == 1) { break; } if (i == 2) { return [i, j]; } } } }; out()",
+            "[2, 0]",
+        ),
+        ("let x = 5; for (x in [1, 2]) { x }; x", "5"),
+        ("for (i in range(5)) { let y = i; }; range(1, 10, 2)", "range(1, 10, 2)"),
+        // Every iteration has its own bindings, which closures created in it keep
+        (
+            "let fs = []; for (i in range(3)) { fs = push(fs, fn() { i }); }; [fs[0](), fs[1](), fs[2]()]",
+            "[0, 1, 2]",
+        ),
+        (
+            "let fs = []; for (i in range(4)) { let j = i * 10; if (i == 1) { continue; } fs = push(fs, fn() { j }); if (i == 2) { break; } }; [fs[0](), fs[1](), len(fs)]",
+            "[0, 20, 2]",
+        ),
+        (
+            "let f = fn() { let fs = []; for (i in [1, 2]) { for (j in [3, 4]) { fs = push(fs, fn() { i * j }); } }; fs }; let fs = f(); [fs[0](), fs[1](), fs[2](), fs[3]()]",
+            "[3, 4, 6, 8]",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input).to_string(), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_for_loop_iteration() {
+    let tests = vec![
+        ("[10, 20, 30]", "10 20 30"),
+        ("\"héllo\"", "h é l l o"),
+        ("range(5)", "0 1 2 3 4"),
+        ("range(2, 5)", "2 3 4"),
+        ("range(10, 0, -3)", "10 7 4 1"),
+        ("range(0, 10, 3)", "0 3 6 9"),
+        ("range(5, 0)", ""),
+        ("range(9223372036854775806, 9223372036854775807, 5)", "9223372036854775806"),
+    ];
+
+    for (input, expected) in tests {
+        let items = ops::iterate(test_eval(input))
+            .unwrap()
+            .map(|item| item.to_string())
+            .collect::<Vec<String>>();
+
+        assert_eq!(items.join(" "), expected, "input: {}", input);
+    }
+
+    let error = test_eval_error("for (x in 5) { x }");
+    assert_eq!(error.kind, EvaluatorErrorKind::TypeMismatch);
+    assert_eq!(error.to_string(), "1:11: Cannot iterate over 5");
+
+    let error = test_eval_error("range(1, 2, 0)");
+    assert_eq!(error.kind, EvaluatorErrorKind::InvalidArgument);
+    assert_eq!(error.message, "range() step cannot be 0");
+}
+
+#[test]
+fn test_assignment() {
+    let tests = vec![
+        ("let x = 1; x = 5; x", 5),
+        ("let x = 1; x = x + 1", 2),
+        ("let x = 10; x += 5; x", 15),
+        ("let x = 10; x -= 5; x", 5),
+        ("let x = 10; x *= 5; x", 50),
+        ("let x = 10; x /= 3; x", 3),
+        ("let x = 10; x %= 3; x", 1),
+        ("let a = 0; let b = 0; a = b = 7; a + b", 14),
+        ("let i = 0; while (i < 10) { i += 1; }; i", 10),
+        ("let sum = 0; for (x in range(1, 5)) { sum += x; }; sum", 10),
+        // Assigning from a closure updates the binding it was defined in
+        (
+            "let counter = fn() { let count = 0; fn() { count += 1 } }; let next = counter(); next(); next(); next()",
+            3,
+        ),
+        ("let x = 1; let f = fn() { x = 10; }; f(); x", 10),
+        ("let f = fn() { let x = 1; let g = fn() { fn() { x *= 4 } }; g()(); x }; f()", 4),
+        // A let inside of a function still shadows instead of assigning
+        ("let x = 1; let f = fn() { let x = 2; x = 3; }; f(); x", 1),
+    ];
+
+    for (input, expected) in tests {
+        test_integer_object(test_eval(input), expected);
+    }
+}
+
+#[test]
+fn test_assignment_errors() {
+    let tests = vec![
+        ("x = 5", EvaluatorErrorKind::IdentifierNotFound("x".to_string()), "1:1: identifier not found: x"),
+        ("y += 1", EvaluatorErrorKind::IdentifierNotFound("y".to_string()), "1:1: identifier not found: y"),
+        ("len = 1", EvaluatorErrorKind::IdentifierNotFound("len".to_string()), "1:1: identifier not found: len"),
+        (
+            "let f = fn() { z = 1 }; f(); let z = 0;",
+            EvaluatorErrorKind::IdentifierNotFound("z".to_string()),
+            "1:16: identifier not found: z",
+        ),
+        ("let s = \"a\"; s -= 1", EvaluatorErrorKind::TypeMismatch, "1:14: Invalid infix expression: (a - 1)"),
+    ];
+
+    for (input, expected_kind, expected_message) in tests {
+        let error = test_eval_error(input);
+
+        assert_eq!(error.kind, expected_kind, "input: {}", input);
+
+        let location = format!("{}: {}", error.span.unwrap(), error.message);
+        assert_eq!(location, expected_message, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_floats() {
+    let tests = vec![
+        ("3.25", "3.25"),
+        ("1e-3", "0.001"),
+        ("2.0", "2.0"),
+        ("-1.5", "-1.5"),
+        ("1.5 + 2", "3.5"),
+        ("2 * 1.5", "3.0"),
+        ("7 / 2.0", "3.5"),
+        ("7 / 2", "3"),
+        ("5.5 % 2", "1.5"),
+        ("1 / 0.0", "inf"),
+        ("0.1 + 0.2 > 0.3", "true"),
+        ("1 == 1.0", "true"),
+        ("2.5 < 3", "true"),
+        ("1.5 != 1.5", "false"),
+        ("\"total: \" + 2.5", "total: 2.5"),
+        ("if (0.5) { 1 } else { 2 }", "1"),
+        ("let wage = 12.5; 8 * 5 * wage * 52", "26000.0"),
+        ("let x = 1; x += 0.5; x", "1.5"),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input).to_string(), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_number_conversion_funcs() {
+    let tests = vec![
+        ("int(3.99)", "3"),
+        ("int(-3.99)", "-3"),
+        ("int(\"42\")", "42"),
+        ("int(true)", "1"),
+        ("int(7)", "7"),
+        ("float(3)", "3.0"),
+        ("float(\" 2.5 \")", "2.5"),
+        ("round(2.5)", "3"),
+        ("round(-2.5)", "-3"),
+        ("round(2.4)", "2"),
+        ("floor(-1.5)", "-2"),
+        ("ceil(1.2)", "2"),
+        ("ceil(4)", "4"),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input).to_string(), expected, "input: {}", input);
+    }
+
+    let tests = vec![
+        ("int(\"abc\")", "Cannot convert \"abc\" to an integer"),
+        ("round(1.0 / 0)", "Cannot convert inf to an integer"),
+        ("float([1])", "Cannot convert [1] to a float"),
+        ("floor(\"1\")", "Cannot floor 1"),
+    ];
+
+    for (input, expected_message) in tests {
+        let error = test_eval_error(input);
+
+        assert_eq!(error.kind, EvaluatorErrorKind::InvalidArgument, "input: {}", input);
+        assert_eq!(error.message, expected_message, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_big_integers() {
+    let tests = vec![
+        ("9223372036854775807 + 1", "9223372036854775808"),
+        ("-9223372036854775807 - 2", "-9223372036854775809"),
+        ("4611686018427387904 * 2", "9223372036854775808"),
+        ("99999999999999999999", "99999999999999999999"),
+        ("-9223372036854775808", "-9223372036854775808"),
+        ("let min = -9223372036854775807 - 1; -min", "9223372036854775808"),
+        ("let min = -9223372036854775807 - 1; min / -1", "9223372036854775808"),
+        // Compound interest in cents, 1% a month for 30 years
+        (
+            "let cents = 1000000000000000000; for (month in range(360)) { cents += cents / 100; }; cents",
+            "35949641327684919054",
+        ),
+        ("let x = 1; for (i in range(100)) { x *= 2; }; x", "1267650600228229401496703205376"),
+        // Results that fit again become regular integers
+        ("(9223372036854775807 + 10) - 20", "9223372036854775797"),
+        ("99999999999999999999 / 99999999999999999999", "1"),
+        // Division truncates and the remainder takes the sign of the left operand
+        ("-99999999999999999999 / 7", "-14285714285714285714"),
+        ("99999999999999999999 % 7", "1"),
+        ("-99999999999999999999 % 7", "-1"),
+        ("99999999999999999999 % -7", "1"),
+        ("99999999999999999999 > 9223372036854775807", "true"),
+        ("-99999999999999999999 < 1", "true"),
+        ("99999999999999999999 == 99999999999999999999", "true"),
+        ("99999999999999999999 != 99999999999999999998", "true"),
+        ("99999999999999999999 * 0.5", "5e19"),
+        ("\"big: \" + 99999999999999999999", "big: 99999999999999999999"),
+        ("{99999999999999999999: 1}[99999999999999999998 + 1]", "1"),
+        ("int(\"123456789012345678901234567890\")", "123456789012345678901234567890"),
+        ("int(1e20)", "100000000000000000000"),
+        ("float(99999999999999999999)", "1e20"),
+        ("float(10 ** 400)", "inf"),
+        ("float(-(10 ** 400))", "-inf"),
+        ("10 ** 400 * 1.0", "inf"),
+        ("-(10 ** 400) < -1e308", "true"),
+        ("if (99999999999999999999) { 1 } else { 2 }", "1"),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input).to_string(), expected, "input: {}", input);
+    }
+
+    // Big integers are only used when needed
+    assert!(matches!(test_eval("9223372036854775807"), Value::Integer(i64::MAX)));
+    assert!(matches!(test_eval("99999999999999999999 - 99999999999999999998"), Value::Integer(1)));
+}
+
+#[test]
+fn test_division_by_zero() {
+    let tests = vec![
+        ("1 / 0", "Division by zero: (1 / 0)"),
+        ("let x = 5; x %= 0", "Division by zero: (5 % 0)"),
+        ("99999999999999999999 / 0", "Division by zero: (99999999999999999999 / 0)"),
+        ("99999999999999999999 % 0", "Division by zero: (99999999999999999999 % 0)"),
+    ];
+
+    for (input, expected_message) in tests {
+        let error = test_eval_error(input);
+
+        assert_eq!(error.kind, EvaluatorErrorKind::DivisionByZero, "input: {}", input);
+        assert_eq!(error.message, expected_message, "input: {}", input);
+    }
+
+    test_integer_object(test_eval("let min = -9223372036854775807 - 1; min % -1"), 0);
+}
+
+#[test]
+fn test_logical_operators() {
+    let tests = vec![
+        ("true && true", true),
+        ("true && false", false),
+        ("false && true", false),
+        ("false || true", true),
+        ("false || false", false),
+        ("true || false", true),
+        ("1 < 2 && 2 < 3", true),
+        ("1 > 2 || 2 > 3", false),
+        ("5 && \"yes\"", false),
+        ("5 && 0.5", true),
+        ("!false && !false", true),
+        ("false || true && false", false),
+    ];
+
+    for (input, expected) in tests {
+        test_boolean_object(test_eval(input), expected);
+    }
+
+    // The right operand is only evaluated when it can change the result
+    let tests = vec![
+        ("let x = 0; false && (x = 1); x", 0),
+        ("let x = 0; true && (x = 1); x", 1),
+        ("let x = 0; true || (x = 1); x", 0),
+        ("let x = 0; false || (x = 1); x", 1),
+        ("let f = fn(n) { n > 0 && f(n - 1) || n == 0 }; if (f(10)) { 1 } else { 0 }", 1),
+    ];
+
+    for (input, expected) in tests {
+        test_integer_object(test_eval(input), expected);
+    }
+
+    // Errors in the skipped operand never happen
+    test_boolean_object(test_eval("false && undefined_name"), false);
+    test_boolean_object(test_eval("true || 1 / 0"), true);
+}
+
+#[test]
+fn test_comparisons() {
+    let tests = vec![
+        ("1 <= 2", true),
+        ("2 <= 2", true),
+        ("3 <= 2", false),
+        ("2 >= 2", true),
+        ("1 >= 2", false),
+        ("1.5 <= 2", true),
+        ("99999999999999999999 >= 99999999999999999999", true),
+        ("1 < 99999999999999999999", true),
+        ("2.5 > 99999999999999999999", false),
+        ("0.0 / 0.0 <= 1", false),
+        ("0.0 / 0.0 >= 1", false),
+        // Strings are compared lexicographically
+        ("\"a\" == \"a\"", true),
+        ("\"a\" != \"b\"", true),
+        ("\"apple\" < \"banana\"", true),
+        ("\"apple\" > \"app\"", true),
+        ("\"Zebra\" < \"apple\"", true),
+        ("\"abc\" <= \"abc\"", true),
+        ("\"abd\" >= \"abc\"", true),
+        ("\"\" < \"a\"", true),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input).to_string(), expected.to_string(), "input: {}", input);
+    }
+
+    let tests = vec![
+        ("\"1\" < 2", "Cannot compare 1 and 2 with <"),
+        ("true > false", "Cannot compare true and false with >"),
+        ("[1] <= [2]", "Cannot compare [1] and [2] with <="),
+    ];
+
+    for (input, expected_message) in tests {
+        let error = test_eval_error(input);
+
+        assert_eq!(error.kind, EvaluatorErrorKind::TypeMismatch, "input: {}", input);
+        assert_eq!(error.message, expected_message, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_equality() {
+    let tests = vec![
+        ("first([]) == last([])", true),
+        ("first([]) != last([])", false),
+        ("if (false) { 1 } == first([])", true),
+        ("1 == 1.0", true),
+        ("1 == true", false),
+        ("1 != \"1\"", true),
+        ("first([]) == false", false),
+        ("0.0 / 0.0 == 0.0 / 0.0", false),
+        ("[1, \"a\", [true]] == [1, \"a\", [true]]", true),
+        ("[1, 2] == [1, 2, 3]", false),
+        ("[1, 2] != [2, 1]", true),
+        ("{\"a\": 1, 2: [3]} == {2: [3], \"a\": 1}", true),
+        ("{\"a\": 1} == {\"a\": 2}", false),
+        ("{\"a\": 1} == {\"b\": 1}", false),
+        ("range(1, 5) == range(1, 5, 1)", true),
+        ("range(5) == [0, 1, 2, 3, 4]", false),
+        ("len == len", true),
+        ("len == first", false),
+        ("let f = fn(x) { x }; f == f", true),
+        ("let f = fn(x) { x }; let g = f; [f] == [g]", true),
+        ("fn(x) { x } == fn(x) { x }", false),
+        ("let make = fn() { fn() { 1 } }; make() == make()", false),
+        ("let f = fn(x) { x }; f == 1", false),
+    ];
+
+    for (input, expected) in tests {
+        test_boolean_object(test_eval(input), expected);
+    }
+}
+
+// Writes the files into a fresh directory, returning a function that gives the absolute path
+// of one of them as a string literal for an import
+fn write_modules(name: &str, files: &[(&str, &str)]) -> impl Fn(&str) -> String {
+    let dir = std::env::temp_dir().join(format!("monkey-{name}-{}", std::process::id()));
+
+    for (file, contents) in files {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    move |file| format!("{:?}", dir.join(file).to_str().unwrap())
+}
+
+#[test]
+fn test_import() {
+    let path = write_modules(
+        "import",
+        &[
+            (
+                "lib/math.mk",
+                r#"import "counter.mk" as counter;
+                let square = fn(x) { x * x };
+                let total = 0;
+                let add = fn(x) { total += x; total };
+                let twice = fn(f, x) { f(f(x)) };
+                let cube = fn(x) { x * square(x) };
+                for (i in range(3)) { total += i; }"#,
+            ),
+            ("lib/counter.mk", "let count = 1;"),
+            ("other.mk", r#"import "lib/counter.mk" as counter; let same = counter;"#),
+        ],
+    );
+    let math = path("lib/math.mk");
+    let module = format!("module {math}");
+
+    let tests = vec![
+        (format!("import {math} as m; m.square(4)"), "16"),
+        (format!("import {math} as m; m.cube(3)"), "27"),
+        (format!("import {math} as m; m.twice(fn(x) {{ x * 10 }}, 2)"), "200"),
+        // The bindings are taken when the module has finished running
+        (format!("import {math} as m; [m.total, m.add(5), m.add(1), m.total]"), "[3, 8, 9, 3]"),
+        // Modules do not see the bindings of the file importing them
+        (format!("let square = 1; import {math} as m; m.square(2) + square"), "5"),
+        (format!("import {math} as m; m.counter.count"), "1"),
+        (format!("import {math} as m;"), &module),
+        // Every import of a file gives the same module, which only runs once
+        (format!("import {math} as a; import {math} as b; a == b"), "true"),
+        (
+            format!(
+                "import {math} as m; import {} as other; m.counter == other.same",
+                path("other.mk")
+            ),
+            "true",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(&input);
+
+        assert_eq!(evaluated.to_string(), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_import_errors() {
+    let path = write_modules(
+        "import-errors",
+        &[
+            ("a.mk", r#"import "b.mk" as b;"#),
+            ("b.mk", r#"import "a.mk" as a;"#),
+            ("self.mk", r#"import "self.mk" as me;"#),
+            ("syntax.mk", "let x = ;\nlet y = 1 +;"),
+            ("boom.mk", "let explode = fn() { 1 / 0 };\nlet value = 2;"),
+            ("crash.mk", "let x = 1;\nx + true;"),
+            ("nested.mk", r#"import "boom.mk" as boom; let run = fn() { boom.explode() };"#),
+        ],
+    );
+
+    let tests = vec![
+        (
+            format!("import {} as a;", path("a.mk")),
+            EvaluatorErrorKind::ImportCycle,
+            r#"Cannot import "a.mk", it is already being imported"#.to_string(),
+        ),
+        (
+            format!("import {} as s;", path("syntax.mk")),
+            EvaluatorErrorKind::ImportFailed,
+            format!("Cannot import {}, it has syntax errors", path("syntax.mk")),
+        ),
+        (
+            format!("import {} as m;", path("missing.mk")),
+            EvaluatorErrorKind::ImportFailed,
+            format!(
+                "Cannot import {}: No such file or directory (os error 2)",
+                path("missing.mk")
+            ),
+        ),
+        (
+            format!("import {} as boom;\nboom.explode()", path("boom.mk")),
+            EvaluatorErrorKind::DivisionByZero,
+            "Division by zero: (1 / 0)".to_string(),
+        ),
+        (
+            format!("import {} as crash;", path("crash.mk")),
+            EvaluatorErrorKind::TypeMismatch,
+            "Invalid infix expression: (1 + true)".to_string(),
+        ),
+        (
+            format!("import {} as boom; boom.missing", path("boom.mk")),
+            EvaluatorErrorKind::IdentifierNotFound("missing".to_string()),
+            format!("module {} has no binding named missing", path("boom.mk")),
+        ),
+        (
+            "let x = 5; x.y".to_string(),
+            EvaluatorErrorKind::TypeMismatch,
+            "Member access not supported: (5.y), 5 is not a module".to_string(),
+        ),
+    ];
+
+    for (input, expected_kind, expected_message) in tests {
+        let error = test_eval_error(&input);
+
+        assert_eq!(error.kind, expected_kind, "input: {}", input);
+        assert_eq!(error.message, expected_message, "input: {}", input);
+    }
+
+    // The cycle is shown with the paths as they were written
+    let error = test_eval_error(&format!("import {} as a;", path("a.mk")));
+    assert_eq!(
+        error.notes.last().unwrap(),
+        &format!(r#"the modules import each other: {} -> "b.mk" -> "a.mk""#, path("a.mk"))
+    );
+
+    let error = test_eval_error(&format!("import {} as me;", path("self.mk")));
+    assert_eq!(
+        error.notes.last().unwrap(),
+        &format!(r#"the modules import each other: {} -> "self.mk""#, path("self.mk"))
+    );
+
+    let syntax = path("syntax.mk");
+    let error = test_eval_error(&format!("import {syntax} as s;"));
+    let syntax = syntax.trim_matches('"');
+    assert_eq!(
+        error.notes,
+        [
+            format!("{syntax}:1:9: no prefix parse function for ;"),
+            format!("{syntax}:2:12: no prefix parse function for ;"),
+        ]
+    );
+
+    // Errors in a module are located where the module was used, the notes say where
+    // they were raised
+    let nested = path("nested.mk");
+    let error = test_eval_error(&format!("import {nested} as nested;\nnested.run()"));
+    assert_eq!(error.span.map(|span| span.to_string()), Some("2:1".to_string()));
+    assert_eq!(
+        error.notes,
+        [
+            format!("raised at {}:1:44", nested.trim_matches('"')),
+            "raised at boom.mk:1:22".to_string(),
+        ]
+    );
+
+    let crash = path("crash.mk");
+    let error = test_eval_error(&format!("import {crash} as crash;"));
+    assert_eq!(error.span.map(|span| span.to_string()), Some("1:1".to_string()));
+    assert_eq!(error.notes[0], format!("raised at {}:2:1", crash.trim_matches('"')));
+}
+
+// Runs the input on both backends after `setup` has registered its functions
+fn run_both(
+    input: &str,
+    setup: impl Fn(&mut Monkey),
+) -> (Result<Value, Error>, Result<Value, Error>) {
+    let run = |backend| {
+        let mut monkey = Monkey::new(backend);
+        setup(&mut monkey);
+        monkey.run(input)
+    };
+
+    (run(Backend::Evaluator), run(Backend::Vm))
+}
+
+#[test]
+fn test_native_functions() {
+    let setup = |monkey: &mut Monkey| {
+        monkey.register_function("double", 1, |args| match &args[0] {
+            Value::Integer(i) => Ok(Value::Integer(i * 2)),
+            arg => Err(EvaluatorError::new(
+                EvaluatorErrorKind::InvalidArgument,
+                format!("double() expects an integer, got {arg}"),
+            )),
+        });
+        // Replaces the builtin
+        monkey.register_function("len", 1, |_| Ok(Value::Integer(42)));
+    };
+
+    let tests = vec![
+        ("double(21)", "42"),
+        ("let f = double; f(2) + double(3)", "10"),
+        ("[1, 2, 3] == [1, 2, 3] && len([1]) == 42", "true"),
+        ("double", "native function double"),
+        ("double == double", "true"),
+        // Bindings of the program shadow host functions
+        ("let double = fn(x) { x * 3 }; double(2)", "6"),
+        ("let f = fn(double) { double }; f(5)", "5"),
+        ("let f = fn() { double(2) }; let a = f(); let double = fn(x) { 0 }; [a, f()]", "[4, 0]"),
+        ("let f = fn() { let g = fn() { len([]) }; let len = fn(x) { 7 }; g() }; f()", "7"),
+    ];
+
+    for (input, expected) in tests {
+        let (evaluated, vm_evaluated) = run_both(input, setup);
+
+        assert_eq!(evaluated.unwrap().to_string(), expected, "input: {}", input);
+        assert_eq!(vm_evaluated.unwrap().to_string(), expected, "vm input: {}", input);
+    }
+
+    let tests = vec![
+        (
+            "double(1, 2)",
+            EvaluatorErrorKind::WrongArgumentCount {
+                expected: 1,
+                given: 2,
+            },
+            "1:1: double() takes 1 argument, 2 arguments given",
+        ),
+        (
+            r#"1 + double("x")"#,
+            EvaluatorErrorKind::InvalidArgument,
+            "1:5: double() expects an integer, got x",
+        ),
+    ];
+
+    for (input, expected_kind, expected_message) in tests {
+        let (evaluated, vm_evaluated) = run_both(input, setup);
+
+        for result in [evaluated, vm_evaluated] {
+            match result {
+                Err(Error::Evaluator(err)) => {
+                    assert_eq!(err.kind, expected_kind, "input: {}", input);
+                    assert_eq!(err.to_string(), expected_message, "input: {}", input);
+                }
+                result => panic!("expected an error for {}, got {:?}", input, result),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_native_functions_in_later_programs() {
+    for backend in [Backend::Evaluator, Backend::Vm] {
+        let mut monkey = Monkey::new(backend);
+        monkey.register_function("host_log", 1, |args| Ok(args[0].clone()));
+
+        assert_eq!(monkey.run("host_log(1)").unwrap().to_string(), "1", "backend: {backend:?}");
+        assert_eq!(monkey.run("host_log(2)").unwrap().to_string(), "2", "backend: {backend:?}");
+
+        match monkey.run("host_log(1, 2)") {
+            Err(Error::Evaluator(err)) => assert_eq!(
+                err.kind,
+                EvaluatorErrorKind::WrongArgumentCount { expected: 1, given: 2 },
+                "backend: {backend:?}"
+            ),
+            result => panic!("expected an error on {backend:?}, got {result:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_render_error_from_earlier_program() {
+    for backend in [Backend::Evaluator, Backend::Vm] {
+        let mut monkey = Monkey::new(backend);
+        monkey.run("let f = fn(s) {      1 / 0 };").unwrap();
+
+        // The error points into the first program so it must not be shown against the second
+        let source = r#"f("ééééééééééééé")"#;
+        let err = monkey.run(source).unwrap_err();
+        let rendered = err.render(source, "<repl>", false);
+
+        assert!(rendered.contains("--> <repl>:1:22\n"), "backend: {backend:?}, got {rendered}");
+        assert!(!rendered.contains(source), "backend: {backend:?}, got {rendered}");
+    }
+}
+
+#[test]
+fn test_native_functions_host_state() {
+    for backend in [Backend::Evaluator, Backend::Vm] {
+        let log = Rc::new(RefCell::new(Vec::new()));
+
+        let mut monkey = Monkey::new(backend);
+        let shared = Rc::clone(&log);
+        monkey.register_function("log", 1, move |args| {
+            shared.borrow_mut().push(args[0].to_string());
+            Ok(Value::Null)
+        });
+        let shared = Rc::clone(&log);
+        monkey.register_function("logged", 0, move |_| {
+            Ok(Value::Integer(shared.borrow().len() as i64))
+        });
+
+        let result = monkey.run(r#"for (x in ["a", "b"]) { log(x); } log(logged()); logged()"#);
+
+        assert_eq!(result.unwrap().to_string(), "3");
+        assert_eq!(*log.borrow(), ["a", "b", "2"]);
+    }
+}
+
+#[test]
+fn test_value_conversions() {
+    assert_eq!(5i32.into_value().to_string(), "5");
+    assert_eq!(u64::MAX.into_value().to_string(), "18446744073709551615");
+    assert_eq!(1.5f32.into_value().to_string(), "1.5");
+    assert_eq!("hi".into_value().to_string(), "hi");
+    assert_eq!(vec![Some(1), None].into_value().to_string(), "[1, null]");
+    assert_eq!((1, "a", true).into_value().to_string(), "[1, a, true]");
+    assert_eq!(
+        HashMap::from([("a".to_string(), vec![1.5])]).into_value().to_string(),
+        "{a: [1.5]}"
+    );
+
+    assert_eq!(i64::from_value(Value::Integer(-3)).unwrap(), -3);
+    assert_eq!(u64::from_value(u64::MAX.into_value()).unwrap(), u64::MAX);
+    assert_eq!(f64::from_value(Value::Integer(2)).unwrap(), 2.0);
+    assert_eq!(f64::from_value(test_eval("-(10 ** 400)")).unwrap(), f64::NEG_INFINITY);
+    assert_eq!(char::from_value("é".into_value()).unwrap(), 'é');
+    assert_eq!(Option::<bool>::from_value(Value::Null).unwrap(), None);
+    assert_eq!(
+        <(String, Vec<i8>)>::from_value(("x", vec![1, 2]).into_value()).unwrap(),
+        ("x".to_string(), vec![1, 2])
+    );
+    assert_eq!(
+        HashMap::<String, i64>::from_value(test_eval(r#"{"a": 1, "b": 2}"#)).unwrap(),
+        HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)])
+    );
+
+    let tests = vec![
+        (u8::from_value(Value::Integer(256)).unwrap_err(), "256 does not fit in u8"),
+        (i64::from_value(Value::Boolean(true)).unwrap_err(), "expected an integer, got true"),
+        (char::from_value("ab".into_value()).unwrap_err(), "expected a single character, got ab"),
+        (
+            Vec::<String>::from_value(vec![1].into_value()).unwrap_err(),
+            "expected a string, got 1",
+        ),
+        (
+            <(i64, i64)>::from_value(vec![1].into_value()).unwrap_err(),
+            "expected an array of 2 elements, got [1]",
+        ),
+        (
+            HashMap::<String, i64>::from_value(test_eval("{1: 2}")).unwrap_err(),
+            "expected a string key, got 1",
+        ),
+    ];
+
+    for (error, expected_message) in tests {
+        assert_eq!(error.message, expected_message);
+    }
+}
+
+#[test]
+fn test_call_script_function() {
+    for backend in [Backend::Evaluator, Backend::Vm] {
+        let mut monkey = Monkey::new(backend);
+        monkey
+            .run(
+                r#"let offset = 10;
+                let add = fn(a, b) { a + b + offset };
+                let describe = fn(name, tags) { f"{name}: {join(tags, ", ")}" };
+                let find = fn(xs, x) { index_of(xs, x) };"#,
+            )
+            .unwrap();
+
+        let add = monkey.run("add").unwrap();
+        let sum: i64 = monkey.call(&add, (1, 2)).unwrap();
+        assert_eq!(sum, 13);
+
+        let sum: f64 = monkey.call(&add, (0.5, 1)).unwrap();
+        assert_eq!(sum, 11.5);
+
+        let describe = monkey.run("describe").unwrap();
+        let description: String = monkey.call(&describe, ("monkey", vec!["fast", "fun"])).unwrap();
+        assert_eq!(description, "monkey: fast, fun");
+
+        let find = monkey.run("find").unwrap();
+        let found: Option<usize> = monkey.call(&find, (vec![3, 4], 4)).unwrap();
+        assert_eq!(found, Some(1));
+        let found: Option<usize> = monkey.call(&find, (vec![3, 4], 5)).unwrap();
+        assert_eq!(found, None);
+
+        // Builtins can be called too
+        let len = monkey.run("len").unwrap();
+        let length: i64 = monkey.call(&len, ("abc",)).unwrap();
+        assert_eq!(length, 3);
+
+        // Errors from the call and from converting the result
+        let error = monkey.call::<i64>(&add, (1,)).unwrap_err();
+        assert_eq!(error.to_string(), "fn(a, b) takes 2 arguments, 1 arguments given");
+
+        let error = monkey.call::<String>(&add, (1, 2)).unwrap_err();
+        assert_eq!(error.to_string(), "expected a string, got 13");
+
+        let error = monkey.call::<i64>(&add, (1, true)).unwrap_err();
+        assert_eq!(error.to_string(), "2:38: Invalid infix expression: (1 + true)");
+    }
+}
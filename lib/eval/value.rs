@@ -1,40 +1,153 @@
-use std::{fmt::Display, rc::Rc, cell::RefCell};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt::{Debug, Display},
+    rc::Rc,
+};
 
-use crate::parser::ast::Statement;
+use num_bigint::{BigInt, Sign};
 
-use super::{env::Environment, funcs::BuiltInFunctionType};
+use crate::{parser::ast::Statement, vm::frame::Closure};
+
+use super::{
+    env::Environment,
+    funcs::{BuiltIn, NativeFunction},
+    module::Module,
+};
 
 pub trait Truth {
     fn truth(&self) -> bool;
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum Value {
     Integer(i64),
+    // An integer outside of the range of i64, smaller integers are always a Value::Integer
+    BigInt(BigInt),
+    Float(f64),
     Boolean(bool),
     String(String),
+    Array(Vec<Value>),
+    Hash(BTreeMap<HashKey, Value>),
+    // Produced by the `range` builtin, the numbers are only generated while iterating
+    Range(Range),
     Return(Box<Value>), 
+    // Signals raised by `break` and `continue`, consumed by the enclosing loop
+    Break,
+    Continue,
     Function {
         params: Vec<String>,
         body: Box<Statement>, // Statement::BlockStatement
         env: Rc<RefCell<Environment>>,
     },
     BuiltInFunction {
-        func: BuiltInFunctionType,
+        func: BuiltIn,
     },
+    // A function registered by the host program
+    NativeFunction(Rc<NativeFunction>),
+    // A function compiled to bytecode for the `Vm`
+    Closure(Rc<Closure>),
+    // Another file brought in with `import`, its bindings are accessed with `module.name`
+    Module(Rc<Module>),
+    // The state of a for loop running on the `Vm`, never visible to programs
+    Iterator(Rc<RefCell<ValueIter>>),
+    #[default]
     Null,
 }
 
+// The subset of values that can be used as the key of a hash
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HashKey {
+    Integer(i64),
+    BigInt(BigInt),
+    Boolean(bool),
+    String(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub step: i64,
+}
+
+impl Range {
+    // Counts from start up to (or down to) end, excluding end itself
+    pub fn iter(self) -> impl Iterator<Item = i64> {
+        let Range { start, end, step } = self;
+
+        std::iter::successors(Some(start), move |i| i.checked_add(step))
+            .take_while(move |i| if step > 0 { *i < end } else { *i > end })
+    }
+}
+
+// Iterates over the elements of a value in a for loop
+pub struct ValueIter(Box<dyn Iterator<Item = Value>>);
+
+impl ValueIter {
+    pub fn new(iter: impl Iterator<Item = Value> + 'static) -> Self {
+        ValueIter(Box::new(iter))
+    }
+}
+
+impl Iterator for ValueIter {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl Debug for ValueIter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ValueIter")
+    }
+}
+
 impl Value {
     pub fn is_null(&self) -> bool {
         matches!(self, Value::Null)
     }
+
+    pub fn hash_key(&self) -> Option<HashKey> {
+        match self {
+            Value::Integer(i) => Some(HashKey::Integer(*i)),
+            Value::BigInt(i) => Some(HashKey::BigInt(i.clone())),
+            Value::Boolean(b) => Some(HashKey::Boolean(*b)),
+            Value::String(s) => Some(HashKey::String(s.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl From<HashKey> for Value {
+    fn from(value: HashKey) -> Self {
+        match value {
+            HashKey::Integer(i) => Value::Integer(i),
+            HashKey::BigInt(i) => Value::BigInt(i),
+            HashKey::Boolean(b) => Value::Boolean(b),
+            HashKey::String(s) => Value::String(s),
+        }
+    }
+}
+
+impl Display for HashKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashKey::Integer(i) => write!(f, "{i}"),
+            HashKey::BigInt(i) => write!(f, "{i}"),
+            HashKey::Boolean(b) => write!(f, "{b}"),
+            HashKey::String(s) => write!(f, "{s}"),
+        }
+    }
 }
 
 impl Truth for Value {
     fn truth(&self) -> bool {
         match self {
             Value::Integer(i) => i > &0,
+            Value::BigInt(i) => i.sign() == Sign::Plus,
+            Value::Float(f) => f > &0.0,
             Value::Boolean(b) => *b,
             _ => false
         }
@@ -53,24 +166,79 @@ impl From<i64> for Value {
     }
 }
 
+// Keeps integers that fit in an i64 as a Value::Integer
+impl From<BigInt> for Value {
+    fn from(value: BigInt) -> Self {
+        match i64::try_from(&value) {
+            Ok(i) => Value::Integer(i),
+            Err(_) => Value::BigInt(value),
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(value: Vec<Value>) -> Self {
+        Value::Array(value)
+    }
+}
+
 impl From<String> for Value {
     fn from(value: String) -> Self {
         Value::String(value)
     }
 }
 
+// A single character is a string of length one
+impl From<char> for Value {
+    fn from(value: char) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::Integer(i) => write!(f, "{i}"),
+            Value::BigInt(i) => write!(f, "{i}"),
+            // Debug always includes the fraction or exponent, so 3.0 is not shown as 3
+            Value::Float(x) => write!(f, "{x:?}"),
             Value::Boolean(b) => write!(f, "{b}"),
             Value::String(s) => write!(f, "{s}"),
+            Value::Array(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "[{elements}]")
+            }
+            Value::Hash(pairs) => {
+                let pairs = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{key}: {value}"))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "{{{pairs}}}")
+            }
+            Value::Range(Range { start, end, step }) => write!(f, "range({start}, {end}, {step})"),
             Value::Null => write!(f, "null"),
             Value::Return(v) => write!(f, "{v}"),
+            Value::Break => write!(f, "break"),
+            Value::Continue => write!(f, "continue"),
             Value::Function { params, body, .. } => {
                 write!(f, "fn({}) {{\n{body}\n}}", params.join(", "))
             },
             Value::BuiltInFunction { .. } => write!(f, "builtin function"),
+            Value::NativeFunction(native) => write!(f, "native function {}", native.name),
+            Value::Closure(closure) => write!(f, "{}", closure.func),
+            Value::Module(module) => write!(f, "module {:?}", module.path),
+            Value::Iterator(_) => write!(f, "iterator"),
         }
     }
 }
@@ -1,4 +1,10 @@
-use crate::{token, token::Token};
+use num_bigint::BigInt;
+
+use crate::{
+    error::{LexerError, LexerErrorKind},
+    token,
+    token::{Span, Token},
+};
 
 #[cfg(test)]
 mod tests;
@@ -7,7 +13,16 @@ pub struct Lexer<'a> {
     input: &'a str,
     pos: usize,
     next_pos: usize,
-    ch: u8,
+    ch: char,
+    // Line and column of the character at `pos`
+    line: usize,
+    column: usize,
+    // The interpolated strings we are inside of, with where each one starts and how many
+    // braces are open in its current expression
+    templates: Vec<(Span, usize)>,
+    // Whether `///` comments are returned as tokens instead of being skipped
+    doc_comments: bool,
+    pub errors: Vec<LexerError>,
 }
 
 impl<'a> Lexer<'a> {
@@ -16,7 +31,12 @@ impl<'a> Lexer<'a> {
             input,
             pos: 0,
             next_pos: 0,
-            ch: 0,
+            ch: '\0',
+            line: 1,
+            column: 0,
+            templates: Vec::new(),
+            doc_comments: false,
+            errors: Vec::new(),
         };
 
         lexer.read_next_char();
@@ -24,34 +44,175 @@ impl<'a> Lexer<'a> {
         lexer
     }
 
+    // Returns doc comments (`/// ...`) as tokens so tools can read them, the parser does not
+    // accept them so this is not used when running a program
+    pub fn with_doc_comments(mut self) -> Self {
+        self.doc_comments = true;
+        self
+    }
+
     fn read_next_char(&mut self) {
-        if self.next_pos >= self.input.len() {
-            self.ch = 0;
+        if self.ch == '\n' {
+            self.line += 1;
+            self.column = 1;
         } else {
-            self.ch = self.input.as_bytes()[self.next_pos];
+            self.column += 1;
         }
 
+        // The input is read one character at a time, past the end it is '\0'
+        self.ch = self.input[self.next_pos.min(self.input.len())..]
+            .chars()
+            .next()
+            .unwrap_or('\0');
+
         self.pos = self.next_pos;
-        self.next_pos += 1;
+        self.next_pos += self.ch.len_utf8();
     }
 
-    fn read_while(&mut self, mut predicate: impl FnMut(u8) -> bool) {
+    fn read_while(&mut self, mut predicate: impl FnMut(char) -> bool) {
         while predicate(self.ch) {
             self.read_next_char()
         }
     }
 
-    fn is_next_char(&self, check: u8) -> bool {
-        self.next_pos < self.input.len() && check == self.input.as_bytes()[self.next_pos]
+    fn is_next_char(&self, check: char) -> bool {
+        self.peek_char(1) == check
+    }
+
+    // Looks `offset` characters past the current one without consuming anything
+    fn peek_char(&self, offset: usize) -> char {
+        self.input[self.pos.min(self.input.len())..]
+            .chars()
+            .nth(offset)
+            .unwrap_or('\0')
     }
 
     pub fn next_token(&mut self) -> Token {
-        self.read_while(|cha| cha.is_ascii_whitespace());
+        self.next_spanned_token().0
+    }
+
+    // Returns the next token along with its location in the source code
+    pub fn next_spanned_token(&mut self) -> (Token, Span) {
+        self.skip_whitespace_and_comments();
+
+        let mut span = Span {
+            start: self.pos.min(self.input.len()),
+            end: 0,
+            line: self.line,
+            column: self.column,
+        };
+
+        let errors = self.errors.len();
+        let tok = self.read_token();
+
+        span.end = self.pos.min(self.input.len());
+
+        // Some illegal tokens have already reported a more specific error
+        if tok == token![ILLEGAL] && self.errors.len() == errors {
+            let illegal = &self.input[span.start..span.end];
+
+            self.errors.push(
+                LexerError::new(
+                    LexerErrorKind::IllegalCharacter,
+                    format!("illegal character '{illegal}'"),
+                )
+                .with_span(span),
+            );
+        }
+
+        (tok, span)
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        loop {
+            self.read_while(|cha| cha.is_whitespace());
+
+            match (self.ch, self.peek_char(1)) {
+                ('/', '/') if !(self.doc_comments && self.is_doc_comment()) => {
+                    self.read_while(|cha| cha != '\n' && cha != '\0');
+                }
+                ('/', '*') => self.skip_block_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    // Block comments can be nested, so `/* a /* b */ c */` is a single comment
+    fn skip_block_comment(&mut self) {
+        let open = Span {
+            end: self.pos + 2,
+            ..self.location()
+        };
+        let mut depth = 0;
+
+        loop {
+            match (self.ch, self.peek_char(1)) {
+                ('/', '*') => depth += 1,
+                ('*', '/') => depth -= 1,
+                ('\0', _) if self.pos >= self.input.len() => {
+                    self.errors.push(
+                        LexerError::new(
+                            LexerErrorKind::UnterminatedComment,
+                            "unterminated block comment",
+                        )
+                        .with_span(open)
+                        .with_note("the comment starts here but is never closed"),
+                    );
+                    return;
+                }
+                _ => {
+                    self.read_next_char();
+                    continue;
+                }
+            }
+
+            // Skip both characters of the delimiter
+            self.read_next_char();
+            self.read_next_char();
+
+            if depth == 0 {
+                return;
+            }
+        }
+    }
+
+    // `///` starts a doc comment but `////` is a regular comment, like in Rust
+    fn is_doc_comment(&self) -> bool {
+        self.ch == '/'
+            && self.peek_char(1) == '/'
+            && self.peek_char(2) == '/'
+            && self.peek_char(3) != '/'
+    }
+
+    fn read_doc_comment(&mut self) -> Token {
+        // Skip the `///`
+        for _ in 0..3 {
+            self.read_next_char();
+        }
+
+        let start_pos = self.pos;
+        self.read_while(|cha| cha != '\n' && cha != '\0');
+
+        let text = self.input[start_pos..self.pos].trim_end_matches('\r');
+
+        token![DOC(text.to_string())]
+    }
+
+    // Picks between an operator and its variant followed by `=`, like `+` and `+=`
+    fn operator_or_assign(&mut self, operator: Token, assign: Token) -> Token {
+        if self.is_next_char('=') {
+            self.read_next_char();
+            assign
+        } else {
+            operator
+        }
+    }
 
+    fn read_token(&mut self) -> Token {
         let tok = match self.ch {
-            b'=' => {
+            '=' => {
                 // ==
-                if self.is_next_char(b'=') {
+                if self.is_next_char('=') {
                     // Consume the next char
                     self.read_next_char();
                     token![==]
@@ -59,39 +220,77 @@ impl<'a> Lexer<'a> {
                     token![=]
                 }
             }
-            b'!' => {
+            '!' => {
                 // !=
-                if self.is_next_char(b'=') {
+                if self.is_next_char('=') {
                     self.read_next_char();
                     token![!=]
                 } else {
                     token![!]
                 }
             }
-            b'+' => token![+],
-            b'-' => token![-],
-            b'*' => token![*],
-            b'/' => token![/],
-            b'%' => token![%],
-            b'<' => token![<],
-            b'>' => token![>],
+            // The arithmetic operators and their compound assignments (+=, -=, ...)
+            '+' => self.operator_or_assign(token![+], token![+=]),
+            '-' => self.operator_or_assign(token![-], token![-=]),
+            '*' if self.is_next_char('*') => {
+                self.read_next_char();
+                token![**]
+            }
+            '*' => self.operator_or_assign(token![*], token![*=]),
+            '/' if self.doc_comments && self.is_doc_comment() => return self.read_doc_comment(),
+            '/' => self.operator_or_assign(token![/], token![/=]),
+            '%' => self.operator_or_assign(token![%], token![%=]),
+            // Single & and | are not operators
+            '&' if self.is_next_char('&') => {
+                self.read_next_char();
+                token![&&]
+            }
+            '|' if self.is_next_char('|') => {
+                self.read_next_char();
+                token![||]
+            }
+            '<' => self.operator_or_assign(token![<], token![<=]),
+            '>' => self.operator_or_assign(token![>], token![>=]),
 
-            b';' => token![;],
-            b',' => token![,],
+            ';' => token![;],
+            ',' => token![,],
+            ':' => token![:],
+            '.' => token![.],
 
-            b'(' => token!['('],
-            b')' => token![')'],
-            b'{' => token!['{'],
-            b'}' => token!['}'],
+            '(' => token!['('],
+            ')' => token![')'],
+            '{' => {
+                if let Some((_, depth)) = self.templates.last_mut() {
+                    *depth += 1;
+                }
+                token!['{']
+            }
+            // The brace that closes an expression inside an interpolated string
+            '}' if matches!(self.templates.last(), Some((_, 0))) => self.read_template_part(),
+            '}' => {
+                if let Some((_, depth)) = self.templates.last_mut() {
+                    *depth -= 1;
+                }
+                token!['}']
+            }
+            '[' => token!['['],
+            ']' => token![']'],
 
-            b'\0' => token![EOF],
+            // The end of the input inside the expression of an interpolated string
+            '\0' if !self.templates.is_empty() => {
+                self.unterminated_string(self.location());
+                return token![ILLEGAL];
+            }
+            '\0' => token![EOF],
             // This makes sure that the identifier consists of letters and/or underscores
-            b if is_identifier_or_keyword(b) => return self.identifier_or_keyword(),
+            'r' if self.is_raw_string() => self.read_raw_string(),
+            'f' if self.peek_char(1) == '"' => self.read_template(),
+            c if is_identifier_start(c) => return self.identifier_or_keyword(),
 
             // Since we modify the position in this statement and the statement above we do not
             // want to to modify it again after the switch statement with the extra `self.read_char`
-            b if b.is_ascii_digit() => return self.number(),
-            b'"' => self.read_string(),
+            c if c.is_ascii_digit() => return self.number(),
+            '"' => self.read_string(),
             _ => token![ILLEGAL],
         };
 
@@ -103,7 +302,7 @@ impl<'a> Lexer<'a> {
     fn identifier_or_keyword(&mut self) -> Token {
         let start_pos = self.pos;
         
-        self.read_while(is_identifier_or_keyword);
+        self.read_while(is_identifier_continue);
 
         let identifier = &self.input[start_pos..self.pos];
 
@@ -111,31 +310,348 @@ impl<'a> Lexer<'a> {
     }
 
     fn number(&mut self) -> Token {
+        // Integers can also be written in hexadecimal (0xFF), octal (0o17) and binary (0b1010)
+        match (self.ch, self.peek_char(1)) {
+            ('0', 'x' | 'X') => return self.radix_integer(16, "hexadecimal"),
+            ('0', 'o' | 'O') => return self.radix_integer(8, "octal"),
+            ('0', 'b' | 'B') => return self.radix_integer(2, "binary"),
+            _ => {}
+        }
+
         let start_pos = self.pos;
-        // Loop while the character is a digit
-        self.read_while(|cha| cha.is_ascii_digit());
+        // Loop while the character is a digit, underscores can separate them like in 1_000_000
+        self.read_while(is_digit_or_separator);
+
+        let mut is_float = false;
 
-        let int_str = &self.input[start_pos..self.pos];
+        // A fraction has to start with a digit so that `1.` is not a float
+        if self.ch == '.' && self.peek_char(1).is_ascii_digit() {
+            is_float = true;
+            self.read_next_char();
+            self.read_while(is_digit_or_separator);
+        }
 
-        let int = int_str.parse::<i64>().unwrap();
+        // An exponent like `1e-3` or `2.5E10`, without digits the `e` is left for an identifier
+        if matches!(self.ch, 'e' | 'E') {
+            let sign = matches!(self.peek_char(1), '+' | '-') as usize;
+
+            if self.peek_char(1 + sign).is_ascii_digit() {
+                is_float = true;
+                for _ in 0..=sign {
+                    self.read_next_char();
+                }
+                self.read_while(is_digit_or_separator);
+            }
+        }
 
-        token![INT(int)]
+        let number = self.input[start_pos..self.pos].replace('_', "");
+
+        if is_float {
+            // Any sequence of digits is a valid float, too large ones become infinity
+            return token![FLOAT(number.parse::<f64>().unwrap())];
+        }
+
+        // Only a literal that does not fit in an i64 can fail to parse as one
+        match number.parse::<i64>() {
+            Ok(int) => token![INT(int)],
+            Err(_) => token![BIGINT(number.parse::<BigInt>().unwrap())],
+        }
+    }
+
+    // Reads an integer literal with a `0x`, `0o` or `0b` prefix
+    fn radix_integer(&mut self, radix: u32, name: &str) -> Token {
+        let start = self.location();
+
+        // Skip the prefix
+        self.read_next_char();
+        self.read_next_char();
+
+        // Letters and digits that are not valid in this base are still part of the literal so
+        // that `0b102` is reported instead of being read as `0b10` followed by `2`
+        let digits_start = self.pos;
+        self.read_while(is_identifier_continue);
+
+        let span = Span {
+            end: self.pos,
+            ..start
+        };
+        let literal = &self.input[start.start..self.pos];
+        let digits = self.input[digits_start..self.pos].replace('_', "");
+
+        let message = match digits.chars().find(|c| !c.is_digit(radix)) {
+            Some(invalid) => format!("invalid digit '{invalid}' in {name} literal '{literal}'"),
+            None if digits.is_empty() => format!("{name} literal '{literal}' has no digits"),
+            None => String::new(),
+        };
+
+        if !message.is_empty() {
+            self.errors
+                .push(LexerError::new(LexerErrorKind::InvalidNumber, message).with_span(span));
+            return token![ILLEGAL];
+        }
+
+        match i64::from_str_radix(&digits, radix) {
+            Ok(int) => token![INT(int)],
+            Err(_) => token![BIGINT(BigInt::parse_bytes(digits.as_bytes(), radix).unwrap())],
+        }
+    }
+
+    // The location of the current character, used to point errors at parts of a token
+    fn location(&self) -> Span {
+        Span {
+            start: self.pos,
+            end: self.next_pos,
+            line: self.line,
+            column: self.column,
+        }
     }
 
     fn read_string(&mut self) -> Token {
+        let open = self.location();
+
         // go to start of string (advance past " character)
         self.read_next_char();
 
+        match self.read_string_part(open, false) {
+            Some(value) => token![STR(value)],
+            None => token![ILLEGAL],
+        }
+    }
+
+    // An interpolated string like `f"Total: {x} items"`, the lexer returns to the string
+    // once it reaches the `}` that closes the expression
+    fn read_template(&mut self) -> Token {
+        let open = self.location();
+
+        // Skip the `f` and the quote
+        self.read_next_char();
+        self.read_next_char();
+
+        let Some(value) = self.read_string_part(open, true) else {
+            return token![ILLEGAL];
+        };
+
+        // Without any expressions it is just a string
+        if self.ch == '"' {
+            return token![STR(value)];
+        }
+
+        self.templates.push((open, 0));
+        token![TEMPLATE_HEAD(value)]
+    }
+
+    fn read_template_part(&mut self) -> Token {
+        let (open, _) = *self.templates.last().unwrap();
+
+        // Skip the `}`
+        self.read_next_char();
+
+        let Some(value) = self.read_string_part(open, true) else {
+            self.templates.pop();
+            return token![ILLEGAL];
+        };
+
+        if self.ch == '"' {
+            self.templates.pop();
+            token![TEMPLATE_TAIL(value)]
+        } else {
+            token![TEMPLATE_MIDDLE(value)]
+        }
+    }
+
+    // Reads the text of a string up to its closing quote, or up to the `{` that starts an
+    // expression when the string is interpolated, in which `{{` and `}}` are literal braces
+    fn read_string_part(&mut self, open: Span, interpolated: bool) -> Option<String> {
+        let mut value = String::new();
+        let mut segment_start = self.pos;
+
+        // Strings can span multiple lines, the newlines are kept in the value
+        loop {
+            match self.ch {
+                '"' => break,
+                '{' | '}' if interpolated && self.peek_char(1) == self.ch => {
+                    value.push_str(&self.input[segment_start..self.next_pos]);
+                    self.read_next_char();
+                    segment_start = self.next_pos;
+                }
+                '{' if interpolated => break,
+                '\\' => {
+                    value.push_str(&self.input[segment_start..self.pos]);
+
+                    let escape = self.location();
+                    self.read_next_char();
+                    if self.ch == '\0' && self.pos >= self.input.len() {
+                        self.unterminated_string(open);
+                        return None;
+                    }
+
+                    if let Some(c) = self.read_escape(escape) {
+                        value.push(c);
+                    }
+                    segment_start = self.next_pos;
+                }
+                '\0' if self.pos >= self.input.len() => {
+                    self.unterminated_string(open);
+                    return None;
+                }
+                _ => {}
+            }
+
+            self.read_next_char();
+        }
+
+        value.push_str(&self.input[segment_start..self.pos]);
+
+        Some(value)
+    }
+
+    // Reads the escape after a backslash, leaving the lexer on its last character
+    // Returns None after reporting an invalid escape so the rest of the string is still read
+    fn read_escape(&mut self, escape: Span) -> Option<char> {
+        let c = match self.ch {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            'u' => return self.read_unicode_escape(escape),
+            _ => {
+                let sequence = &self.input[escape.start..self.next_pos];
+                let message = format!("unknown character escape '{sequence}'");
+
+                self.invalid_escape(escape, message);
+                return None;
+            }
+        };
+
+        Some(c)
+    }
+
+    // `\u{1F600}` with 1 to 6 hex digits naming a unicode scalar value
+    fn read_unicode_escape(&mut self, escape: Span) -> Option<char> {
+        if self.peek_char(1) != '{' {
+            self.invalid_escape(escape, "expected '{' after '\\u'".to_string());
+            return None;
+        }
+        self.read_next_char();
+
+        let digits_start = self.next_pos;
+        while self.peek_char(1).is_ascii_hexdigit() {
+            self.read_next_char();
+        }
+        let digits = &self.input[digits_start..self.next_pos];
+
+        if self.peek_char(1) != '}' {
+            self.invalid_escape(escape, "unterminated unicode escape".to_string());
+            return None;
+        }
+        self.read_next_char();
+
+        let sequence = &self.input[escape.start..self.next_pos];
+        let c = (1..=6)
+            .contains(&digits.len())
+            .then(|| u32::from_str_radix(digits, 16).ok())
+            .flatten()
+            .and_then(char::from_u32);
+
+        if c.is_none() {
+            let message = format!("invalid unicode escape '{sequence}'");
+            self.invalid_escape(escape, message);
+        }
+
+        c
+    }
+
+    fn invalid_escape(&mut self, escape: Span, message: String) {
+        let span = Span {
+            end: self.next_pos,
+            ..escape
+        };
+
+        self.errors.push(
+            LexerError::new(LexerErrorKind::InvalidEscape, message)
+                .with_span(span)
+                .with_note(r#"valid escapes are \n, \t, \r, \0, \\, \", \' and \u{...}"#),
+        );
+    }
+
+    // Raw strings like `r"C:\path"` do not process escapes, and any number of `#` can be
+    // placed around the quotes so that the string can contain them: `r#"say "hi""#`
+    fn read_raw_string(&mut self) -> Token {
+        let open = self.location();
+
+        // Skip the `r`
+        self.read_next_char();
+
+        let mut hashes = 0;
+        while self.ch == '#' {
+            hashes += 1;
+            self.read_next_char();
+        }
+
+        // Skip the opening quote
+        self.read_next_char();
         let start_pos = self.pos;
-        // Loop while the character is a digit
-        self.read_while(|cha| cha != b'"');
 
-        let str = &self.input[start_pos..self.pos];
+        loop {
+            if self.ch == '"' && (1..=hashes).all(|i| self.peek_char(i) == '#') {
+                break;
+            }
+            if self.pos >= self.input.len() {
+                self.unterminated_string(open);
+                return token![ILLEGAL];
+            }
+            self.read_next_char();
+        }
 
-        token![STR(str.to_string())]
+        let value = self.input[start_pos..self.pos].to_string();
+
+        // Stop on the last closing character
+        for _ in 0..hashes {
+            self.read_next_char();
+        }
+
+        token![STR(value)]
     }
 
+    // Whether the lexer is at the `r` that starts a raw string
+    fn is_raw_string(&self) -> bool {
+        let mut offset = 1;
+        while self.peek_char(offset) == '#' {
+            offset += 1;
+        }
+
+        self.ch == 'r' && self.peek_char(offset) == '"'
+    }
+
+    // Inside an interpolated string the quote that was meant to close it starts a new
+    // string instead, so the interpolated string is the one reported
+    fn unterminated_string(&mut self, open: Span) {
+        let open = match self.templates.first() {
+            Some((template, _)) => *template,
+            None => open,
+        };
+        self.templates.clear();
+
+        self.errors.push(
+            LexerError::new(LexerErrorKind::UnterminatedString, "unterminated string literal")
+                .with_span(open)
+                .with_note("the string starts here but is never closed"),
+        );
+    }
 }
-fn is_identifier_or_keyword(check: u8) -> bool {
-    matches!(check, b'a'..=b'z' | b'A'..=b'Z' | b'_')
+
+// Identifiers follow the Unicode XID rules like Rust's do, so `größe` and `变量` are valid
+fn is_identifier_start(check: char) -> bool {
+    check == '_' || unicode_ident::is_xid_start(check)
+}
+
+fn is_identifier_continue(check: char) -> bool {
+    unicode_ident::is_xid_continue(check)
+}
+
+fn is_digit_or_separator(check: char) -> bool {
+    check.is_ascii_digit() || check == '_'
 }
@@ -130,8 +130,9 @@ fn test_next_token_reserved() {
 
 #[test]
 fn test_next_token_operators() {
+    // `/*` would start a block comment
     let input = r#"
-        !-/*5
+        !-/ *5
         5 < 10 > 5"#;
 
     let tests = vec![
@@ -160,7 +161,11 @@ fn test_next_token_operators() {
 fn test_next_token_double_char() {
     let input = r#"
         10 == 10;
-        10 != 9;"#;
+        10 != 9;
+        += -= *= /= %=
+        && || & |
+        <= >= < >
+        ** * **="#;
 
     let tests = vec![
         token![INT(10)],
@@ -171,6 +176,23 @@ fn test_next_token_double_char() {
         token![!=],
         token![INT(9)],
         token![;],
+        token![+=],
+        token![-=],
+        token![*=],
+        token![/=],
+        token![%=],
+        token![&&],
+        token![||],
+        token![ILLEGAL],
+        token![ILLEGAL],
+        token![<=],
+        token![>=],
+        token![<],
+        token![>],
+        token![**],
+        token![*],
+        token![**],
+        token![=],
     ];
 
     let mut lexer = Lexer::new(input);
@@ -181,3 +203,434 @@ fn test_next_token_double_char() {
         assert_eq!(expect, tok);
     }
 }
+
+#[test]
+fn test_next_token_brackets() {
+    let input = "[1, 2][0];";
+
+    let tests = vec![
+        token!['['],
+        token![INT(1)],
+        token![,],
+        token![INT(2)],
+        token![']'],
+        token!['['],
+        token![INT(0)],
+        token![']'],
+        token![;],
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let tok = lexer.next_token();
+
+        assert_eq!(expect, tok);
+    }
+}
+
+#[test]
+fn test_next_token_hash() {
+    let input = r#"{"foo": "bar"}"#;
+
+    let tests = vec![
+        token!['{'],
+        token![STR("foo".to_string())],
+        token![:],
+        token![STR("bar".to_string())],
+        token!['}'],
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let tok = lexer.next_token();
+
+        assert_eq!(expect, tok);
+    }
+}
+
+#[test]
+fn test_next_token_spans() {
+    let input = "let x = 5;\n  \"hi\" == x;";
+
+    let tests = vec![
+        (token![LET], Span { start: 0, end: 3, line: 1, column: 1 }),
+        (token![IDENT("x")], Span { start: 4, end: 5, line: 1, column: 5 }),
+        (token![=], Span { start: 6, end: 7, line: 1, column: 7 }),
+        (token![INT(5)], Span { start: 8, end: 9, line: 1, column: 9 }),
+        (token![;], Span { start: 9, end: 10, line: 1, column: 10 }),
+        (token![STR("hi".to_string())], Span { start: 13, end: 17, line: 2, column: 3 }),
+        (token![==], Span { start: 18, end: 20, line: 2, column: 8 }),
+        (token![IDENT("x")], Span { start: 21, end: 22, line: 2, column: 11 }),
+        (token![;], Span { start: 22, end: 23, line: 2, column: 12 }),
+        (token![EOF], Span { start: 23, end: 23, line: 2, column: 13 }),
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let tok = lexer.next_spanned_token();
+
+        assert_eq!(expect, tok);
+    }
+}
+
+#[test]
+fn test_next_token_loops() {
+    let input = "while (x) { break; continue; } for (c in s) {}";
+
+    let tests = vec![
+        token![WHILE],
+        token!['('],
+        token![IDENT("x".to_string())],
+        token![')'],
+        token!['{'],
+        token![BREAK],
+        token![;],
+        token![CONTINUE],
+        token![;],
+        token!['}'],
+        token![FOR],
+        token!['('],
+        token![IDENT("c".to_string())],
+        token![IN],
+        token![IDENT("s".to_string())],
+        token![')'],
+        token!['{'],
+        token!['}'],
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let tok = lexer.next_token();
+
+        assert_eq!(expect, tok);
+    }
+}
+
+#[test]
+fn test_next_token_floats() {
+    let input = "3.25 1e-3 2.5E+2 10e3 0.5 7 1.x 2e";
+
+    let tests = vec![
+        token![FLOAT(3.25)],
+        token![FLOAT(0.001)],
+        token![FLOAT(250.0)],
+        token![FLOAT(10000.0)],
+        token![FLOAT(0.5)],
+        token![INT(7)],
+        // Without digits after them the dot and the e are not part of the number
+        token![INT(1)],
+        token![.],
+        token![IDENT("x")],
+        token![INT(2)],
+        token![IDENT("e")],
+        token![EOF],
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let tok = lexer.next_token();
+
+        assert_eq!(expect, tok);
+    }
+}
+
+#[test]
+fn test_next_token_big_integers() {
+    let input = "x = 99999999999999999999; 9223372036854775807";
+
+    let tests = vec![
+        token![IDENT("x")],
+        token![=],
+        token![BIGINT("99999999999999999999".parse().unwrap())],
+        token![;],
+        // The largest integer still fits in an i64
+        token![INT(i64::MAX)],
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let tok = lexer.next_token();
+
+        assert_eq!(expect, tok);
+    }
+
+    assert!(lexer.errors.is_empty(), "unexpected lexer errors. got={:?}", lexer.errors);
+}
+
+#[test]
+fn test_next_token_string_escapes() {
+    let input = r#""a\nb\t\"c\"\\" "\u{48}\u{1F600}\0" "line one
+line two" "café""#;
+
+    let tests = vec![
+        token![STR("a\nb\t\"c\"\\".to_string())],
+        token![STR("H\u{1F600}\0".to_string())],
+        token![STR("line one\nline two".to_string())],
+        token![STR("café".to_string())],
+        token![EOF],
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let tok = lexer.next_token();
+
+        assert_eq!(expect, tok);
+    }
+
+    assert!(lexer.errors.is_empty(), "unexpected lexer errors. got={:?}", lexer.errors);
+}
+
+#[test]
+fn test_next_token_raw_strings() {
+    let input = r###"r"C:\path\n" r#"say "hi""# r##"a "# b"## r"two
+lines" raw"###;
+
+    let tests = vec![
+        token![STR(r"C:\path\n".to_string())],
+        token![STR(r#"say "hi""#.to_string())],
+        token![STR(r##"a "# b"##.to_string())],
+        token![STR("two\nlines".to_string())],
+        // Only an `r` right before a quote starts a raw string
+        token![IDENT("raw")],
+        token![EOF],
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let tok = lexer.next_token();
+
+        assert_eq!(expect, tok);
+    }
+
+    assert!(lexer.errors.is_empty(), "unexpected lexer errors. got={:?}", lexer.errors);
+}
+
+#[test]
+fn test_next_token_string_errors() {
+    let tests = vec![
+        (r#""a\qb""#, LexerErrorKind::InvalidEscape, "1:3: unknown character escape '\\q'"),
+        (r#""\u{110000}""#, LexerErrorKind::InvalidEscape, "1:2: invalid unicode escape '\\u{110000}'"),
+        (r#""\u41""#, LexerErrorKind::InvalidEscape, "1:2: expected '{' after '\\u'"),
+        ("let x = 1;\nlet s = \"abc", LexerErrorKind::UnterminatedString, "2:9: unterminated string literal"),
+        ("r#\"abc\"", LexerErrorKind::UnterminatedString, "1:1: unterminated string literal"),
+        // Interpolated strings are reported where they start
+        ("let s = f\"a {1", LexerErrorKind::UnterminatedString, "1:9: unterminated string literal"),
+        ("let s = f\"a {1\"", LexerErrorKind::UnterminatedString, "1:9: unterminated string literal"),
+        ("f\"a {x} b", LexerErrorKind::UnterminatedString, "1:1: unterminated string literal"),
+        ("f\"{ f\"{1} }", LexerErrorKind::UnterminatedString, "1:1: unterminated string literal"),
+    ];
+
+    for (input, kind, message) in tests {
+        let mut lexer = Lexer::new(input);
+        while lexer.next_token() != token![EOF] {}
+
+        assert_eq!(lexer.errors.len(), 1, "unexpected lexer errors for {input}. got={:?}", lexer.errors);
+
+        let error = &lexer.errors[0];
+        assert_eq!(error.kind, kind);
+        assert_eq!(error.message, message.split_once(": ").unwrap().1);
+        assert_eq!(error.span.unwrap().to_string(), message.split_once(": ").unwrap().0);
+    }
+
+    // The rest of the string is still read after an invalid escape
+    let mut lexer = Lexer::new(r#""a\qb" 1"#);
+    assert_eq!(lexer.next_token(), token![STR("ab".to_string())]);
+    assert_eq!(lexer.next_token(), token![INT(1)]);
+}
+
+#[test]
+fn test_next_token_interpolated_strings() {
+    let input = r#"f"Total: {x + 1} items" f"{a}{{b}}{ {"k": f"{c}"}["k"] }" f"plain {{}}""#;
+
+    let tests = vec![
+        token![TEMPLATE_HEAD("Total: ".to_string())],
+        token![IDENT("x")],
+        token![+],
+        token![INT(1)],
+        token![TEMPLATE_TAIL(" items".to_string())],
+        token![TEMPLATE_HEAD("".to_string())],
+        token![IDENT("a")],
+        // Doubled braces are literal text
+        token![TEMPLATE_MIDDLE("{b}".to_string())],
+        // Braces and strings inside of an expression
+        token!['{'],
+        token![STR("k".to_string())],
+        token![:],
+        token![TEMPLATE_HEAD("".to_string())],
+        token![IDENT("c")],
+        token![TEMPLATE_TAIL("".to_string())],
+        token!['}'],
+        token!['['],
+        token![STR("k".to_string())],
+        token![']'],
+        token![TEMPLATE_TAIL("".to_string())],
+        // Without any expressions it is a plain string
+        token![STR("plain {}".to_string())],
+        token![EOF],
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let tok = lexer.next_token();
+
+        assert_eq!(expect, tok);
+    }
+
+    assert!(lexer.errors.is_empty(), "unexpected lexer errors. got={:?}", lexer.errors);
+}
+
+#[test]
+fn test_next_token_unicode() {
+    let input = "let größe = \"日本\";\n_x1 ≠ 変数";
+
+    let tests = vec![
+        (token![LET], Span { start: 0, end: 3, line: 1, column: 1 }),
+        (token![IDENT("größe")], Span { start: 4, end: 11, line: 1, column: 5 }),
+        (token![=], Span { start: 12, end: 13, line: 1, column: 11 }),
+        (token![STR("日本".to_string())], Span { start: 14, end: 22, line: 1, column: 13 }),
+        (token![;], Span { start: 22, end: 23, line: 1, column: 17 }),
+        (token![IDENT("_x1")], Span { start: 24, end: 27, line: 2, column: 1 }),
+        // Symbols are not identifiers, the whole character is illegal
+        (token![ILLEGAL], Span { start: 28, end: 31, line: 2, column: 5 }),
+        (token![IDENT("変数")], Span { start: 32, end: 38, line: 2, column: 7 }),
+        (token![EOF], Span { start: 38, end: 38, line: 2, column: 9 }),
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let tok = lexer.next_spanned_token();
+
+        assert_eq!(expect, tok);
+    }
+
+    assert_eq!(lexer.errors.len(), 1);
+    assert_eq!(lexer.errors[0].message, "illegal character '≠'");
+}
+
+#[test]
+fn test_next_token_number_literals() {
+    let input = "player1 x2y 1_000_000 0xFF 0o17 0b1010 0XdEaD_bEeF 1_000.5e1_0 0x1_0000_0000_0000_0000";
+
+    let tests = vec![
+        token![IDENT("player1")],
+        token![IDENT("x2y")],
+        token![INT(1_000_000)],
+        token![INT(255)],
+        token![INT(15)],
+        token![INT(10)],
+        token![INT(0xDEAD_BEEF)],
+        token![FLOAT(1000.5e10)],
+        token![BIGINT("18446744073709551616".parse().unwrap())],
+        token![EOF],
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let tok = lexer.next_token();
+
+        assert_eq!(expect, tok);
+    }
+
+    assert!(lexer.errors.is_empty(), "unexpected lexer errors. got={:?}", lexer.errors);
+}
+
+#[test]
+fn test_next_token_invalid_number_literals() {
+    let tests = vec![
+        ("0b102", "1:1: invalid digit '2' in binary literal '0b102'"),
+        ("x = 0o8;", "1:5: invalid digit '8' in octal literal '0o8'"),
+        ("0xfg", "1:1: invalid digit 'g' in hexadecimal literal '0xfg'"),
+        ("0x_", "1:1: hexadecimal literal '0x_' has no digits"),
+    ];
+
+    for (input, message) in tests {
+        let mut lexer = Lexer::new(input);
+        while lexer.next_token() != token![EOF] {}
+
+        assert_eq!(lexer.errors.len(), 1, "unexpected lexer errors for {input}. got={:?}", lexer.errors);
+
+        let error = &lexer.errors[0];
+        assert_eq!(error.kind, LexerErrorKind::InvalidNumber);
+        assert_eq!(error.to_string(), message);
+    }
+}
+
+#[test]
+fn test_next_token_comments() {
+    let input = "// a comment
+        let x = 5; // trailing /* not a block */
+        /* a /* nested */ block
+           over lines */ x /= /**/ 2 /// doc
+        //// not a doc comment";
+
+    let tests = vec![
+        token![LET],
+        token![IDENT("x")],
+        token![=],
+        token![INT(5)],
+        token![;],
+        token![IDENT("x")],
+        token![/=],
+        token![INT(2)],
+        token![EOF],
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let tok = lexer.next_token();
+
+        assert_eq!(expect, tok);
+    }
+
+    assert!(lexer.errors.is_empty(), "unexpected lexer errors. got={:?}", lexer.errors);
+}
+
+#[test]
+fn test_next_token_doc_comments() {
+    let input = "/// Adds one\r\n///\nlet f = fn(x) { x + 1 }; //// regular\n// regular\n/** block */";
+
+    let tests = vec![
+        (token![DOC(" Adds one")], Span { start: 0, end: 13, line: 1, column: 1 }),
+        (token![DOC("")], Span { start: 14, end: 17, line: 2, column: 1 }),
+        (token![LET], Span { start: 18, end: 21, line: 3, column: 1 }),
+    ];
+
+    let mut lexer = Lexer::new(input).with_doc_comments();
+
+    for expect in tests {
+        let tok = lexer.next_spanned_token();
+
+        assert_eq!(expect, tok);
+    }
+
+    // The other comments are still skipped
+    while lexer.next_token() != token![;] {}
+    assert_eq!(lexer.next_token(), token![EOF]);
+}
+
+#[test]
+fn test_next_token_unterminated_comment() {
+    let input = "let x = 1;\n/* a /* b */\nlet y = 2;";
+
+    let mut lexer = Lexer::new(input);
+    while lexer.next_token() != token![EOF] {}
+
+    assert_eq!(lexer.errors.len(), 1, "unexpected lexer errors. got={:?}", lexer.errors);
+
+    let error = &lexer.errors[0];
+    assert_eq!(error.kind, LexerErrorKind::UnterminatedComment);
+    assert_eq!(error.span, Some(Span { start: 11, end: 13, line: 2, column: 1 }));
+    assert_eq!(error.message, "unterminated block comment");
+}
@@ -1,21 +1,134 @@
-use eval::{Evaluator, value::Value, EvaluatorErr};
-use parser::{ast::Ast, program::Program};
+use std::{path::PathBuf, rc::Rc};
+
+use compiler::{Bytecode, Compiler};
+use error::{Error, EvaluatorError};
+use eval::{
+    convert::{FromValue, IntoArgs},
+    funcs::NativeFunction,
+    value::Value,
+    Evaluator,
+};
+use lexer::Lexer;
+use parser::{ast::Ast, program::Program, Parser};
+use vm::Vm;
 
 #[macro_use]
 pub mod token;
 
+pub mod compiler;
+pub mod error;
+pub mod eval;
 pub mod lexer;
 pub mod parser;
-pub mod eval;
+pub mod vm;
+
+// How programs are executed, both backends produce the same results
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    // Walks the AST directly
+    #[default]
+    Evaluator,
+    // Compiles the AST to bytecode and runs it on a stack virtual machine
+    Vm,
+}
 
 #[derive(Default)]
 pub struct Monkey {
-    eval: Evaluator
+    backend: Backend,
+    eval: Evaluator,
+    compiler: Compiler,
+    vm: Vm,
+    // The bytecode of the last program run on the vm, which its closures refer to
+    bytecode: Option<Bytecode>,
 }
 
 impl Monkey {
 
-    pub fn eval(&mut self, program: Program) -> Result<Value, EvaluatorErr> {
-        self.eval.eval_self(&Ast::Program(program))
+    pub fn new(backend: Backend) -> Self {
+        Monkey {
+            backend,
+            ..Default::default()
+        }
+    }
+
+    // Makes `random()` and the other random builtins reproducible, the generator is not
+    // seeded from the OS at all once a seed is given
+    pub fn seed(&mut self, seed: u64) {
+        self.eval.seed(seed);
+        self.vm.seed(seed);
+    }
+
+    // Imports are resolved relative to the file they are written in, and imports in the
+    // program itself relative to this directory (the working directory by default)
+    pub fn set_import_dir(&mut self, dir: impl Into<PathBuf>) {
+        let dir = dir.into();
+        self.eval.set_import_dir(dir.clone());
+        self.compiler.set_import_dir(dir);
+    }
+
+    // Lets scripts call `func` as `name(...)` with exactly `arity` arguments
+    //
+    // The closure can capture state shared with the host. Bindings defined by a script
+    // shadow the function, which in turn shadows the builtin with the same name
+    pub fn register_function(
+        &mut self,
+        name: &str,
+        arity: usize,
+        func: impl Fn(&[Value]) -> Result<Value, EvaluatorError> + 'static,
+    ) {
+        let native = Rc::new(NativeFunction::new(name, arity, func));
+        self.eval.register_function(Rc::clone(&native));
+        self.compiler.register_function(native);
+    }
+
+    // Lexes and parses the input, returning every error found in the source
+    pub fn parse(input: &str) -> Result<Program, Error> {
+        let mut parser = Parser::new(Lexer::new(input));
+
+        let program = parser.parse_program();
+
+        if !parser.errors.is_empty() {
+            return Err(Error::Parser(parser.errors));
+        }
+
+        Ok(program)
+    }
+
+    pub fn eval(&mut self, program: Program) -> Result<Value, Error> {
+        match self.backend {
+            Backend::Evaluator => Ok(self.eval.eval_self(&Ast::Program(program))?),
+            Backend::Vm => {
+                let bytecode = self.bytecode.insert(self.compiler.compile(&program)?);
+                Ok(self.vm.run(bytecode)?)
+            }
+        }
+    }
+
+    // Calls a function from a program, like `add` after running `let add = fn(a, b) { a + b };`,
+    // converting the arguments and the result
+    //
+    // let add = monkey.run("add")?;
+    // let sum: i64 = monkey.call(&add, (1, 2))?;
+    pub fn call<R: FromValue>(&mut self, func: &Value, args: impl IntoArgs) -> Result<R, Error> {
+        let args = args.into_args();
+
+        let result = match self.backend {
+            Backend::Evaluator => self.eval.call(func.clone(), args)?,
+            Backend::Vm => {
+                let bytecode = match &mut self.bytecode {
+                    Some(bytecode) => bytecode,
+                    None => self.bytecode.insert(self.compiler.compile(&Program::default())?),
+                };
+                self.vm.call_function(bytecode, func.clone(), args)?
+            }
+        };
+
+        Ok(R::from_value(result)?)
+    }
+
+    // Parses and evaluates the input in one step
+    pub fn run(&mut self, input: &str) -> Result<Value, Error> {
+        let program = Monkey::parse(input)?;
+        self.eval(program)
     }
 }
\ No newline at end of file
@@ -1,6 +1,8 @@
 use std::fmt::Display;
 
-use crate::token::Token;
+use num_bigint::BigInt;
+
+use crate::token::{Span, Token};
 
 use super::program::Program;
 
@@ -12,22 +14,54 @@ pub enum Ast {
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum Statement {
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Statement { kind, span }
+    }
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatementKind {
     LetStatement { ident: Token, value: Expression },
     ReturnStatement(Expression),
     ExpressionStatement(Expression),
     BlockStatement(Vec<Statement>),
+    WhileStatement {
+        condition: Expression,
+        body: Box<Statement>,
+    },
+    // Runs the body for every element of an array, character of a string or number in a range
+    ForStatement {
+        ident: Token,
+        iterable: Expression,
+        body: Box<Statement>,
+    },
+    BreakStatement,
+    ContinueStatement,
+    // `import "path/to/lib.mk" as lib;` binds the top-level bindings of another file to `ident`
+    ImportStatement { path: String, ident: String },
 }
 
-impl Display for Statement {
+impl Display for StatementKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Statement::LetStatement { ident, value } => {
+            StatementKind::LetStatement { ident, value } => {
                 write!(f, "let {ident} = {value};")
             }
-            Statement::ReturnStatement(value) => write!(f, "return {value};"),
-            Statement::ExpressionStatement(value) => write!(f, "{value}"),
-            Statement::BlockStatement(statements) => {
+            StatementKind::ReturnStatement(value) => write!(f, "return {value};"),
+            StatementKind::ExpressionStatement(value) => write!(f, "{value}"),
+            StatementKind::BlockStatement(statements) => {
                 let output = statements
                     .iter()
                     .map(|stmt| stmt.to_string())
@@ -35,13 +69,46 @@ impl Display for Statement {
                     .join("\n");
                 write!(f, "{output}")
             }
+            StatementKind::WhileStatement { condition, body } => {
+                write!(f, "while {condition} {{{body}}}")
+            }
+            StatementKind::ForStatement {
+                ident,
+                iterable,
+                body,
+            } => write!(f, "for ({ident} in {iterable}) {{{body}}}"),
+            StatementKind::BreakStatement => write!(f, "break;"),
+            StatementKind::ContinueStatement => write!(f, "continue;"),
+            StatementKind::ImportStatement { path, ident } => {
+                write!(f, "import {path:?} as {ident};")
+            }
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum Expression {
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, span: Span) -> Self {
+        Expression { kind, span }
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpressionKind {
     IntExpression(i64),
+    BigIntExpression(BigInt),
+    FloatExpression(f64),
     IdentifierExpression(String),
     PrefixExpression {
         op_token: Token,
@@ -54,6 +121,8 @@ pub enum Expression {
     },
     BooleanExpression(bool),
     StringExpression(String),
+    // An interpolated string, the text between the expressions is kept as string expressions
+    TemplateExpression(Vec<Expression>),
     IfExpression {
         condition: Box<Expression>,
         consequence: Box<Statement>,
@@ -67,26 +136,58 @@ pub enum Expression {
         function: Box<Expression>,
         arguments: Vec<Expression>,
     },
+    ArrayExpression(Vec<Expression>),
+    IndexExpression {
+        left: Box<Expression>,
+        index: Box<Expression>,
+    },
+    HashExpression(Vec<(Expression, Expression)>),
+    // `module.name`, a binding of an imported module
+    MemberExpression {
+        left: Box<Expression>,
+        name: String,
+    },
+    // `name = value` or a compound assignment like `name += value`, which evaluates to the
+    // new value of the variable
+    AssignExpression {
+        name: String,
+        op_token: Token,
+        value: Box<Expression>,
+    },
 }
 
-impl Display for Expression {
+impl Display for ExpressionKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Expression::IntExpression(value) => write!(f, "{value}"),
-            Expression::IdentifierExpression(name) => write!(f, "{name}"),
-            Expression::PrefixExpression { op_token, right } => {
+            ExpressionKind::IntExpression(value) => write!(f, "{value}"),
+            ExpressionKind::BigIntExpression(value) => write!(f, "{value}"),
+            ExpressionKind::FloatExpression(value) => write!(f, "{value:?}"),
+            ExpressionKind::IdentifierExpression(name) => write!(f, "{name}"),
+            ExpressionKind::PrefixExpression { op_token, right } => {
                 write!(f, "({op_token}{right})")
             }
-            Expression::InfixExpression {
+            ExpressionKind::InfixExpression {
                 left,
                 op_token,
                 right,
             } => {
                 write!(f, "({left} {op_token} {right})")
             }
-            Expression::BooleanExpression(value) => write!(f, "{value}"),
-            Expression::StringExpression(value) => write!(f, "{value}"),
-            Expression::IfExpression {
+            ExpressionKind::BooleanExpression(value) => write!(f, "{value}"),
+            ExpressionKind::StringExpression(value) => write!(f, "{value}"),
+            ExpressionKind::TemplateExpression(parts) => {
+                let parts = parts
+                    .iter()
+                    .map(|part| match &part.kind {
+                        ExpressionKind::StringExpression(text) => {
+                            text.replace('{', "{{").replace('}', "}}")
+                        }
+                        _ => format!("{{{part}}}"),
+                    })
+                    .collect::<String>();
+                write!(f, "f\"{parts}\"")
+            }
+            ExpressionKind::IfExpression {
                 condition,
                 consequence,
                 alternative,
@@ -97,7 +198,7 @@ impl Display for Expression {
                 }
                 Ok(())
             }
-            Expression::FunctionExpression { parameters, body } => {
+            ExpressionKind::FunctionExpression { parameters, body } => {
                 let params = parameters
                     .iter()
                     .map(|p| p.to_string())
@@ -105,7 +206,7 @@ impl Display for Expression {
                     .join(", ");
                 write!(f, "fn ({params}) {{{body}}}")
             }
-            Expression::CallExpression {
+            ExpressionKind::CallExpression {
                 function,
                 arguments,
             } => {
@@ -116,6 +217,29 @@ impl Display for Expression {
                     .join(", ");
                 write!(f, "{function}({args})")
             }
+            ExpressionKind::ArrayExpression(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "[{elements}]")
+            }
+            ExpressionKind::IndexExpression { left, index } => write!(f, "({left}[{index}])"),
+            ExpressionKind::MemberExpression { left, name } => write!(f, "({left}.{name})"),
+            ExpressionKind::HashExpression(pairs) => {
+                let pairs = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{key}: {value}"))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "{{{pairs}}}")
+            }
+            ExpressionKind::AssignExpression {
+                name,
+                op_token,
+                value,
+            } => write!(f, "({name} {op_token} {value})"),
         }
     }
 }
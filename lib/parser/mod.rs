@@ -5,11 +5,12 @@ pub mod program;
 #[cfg(test)]
 mod tests;
 
+use crate::error::{ParserError, ParserErrorKind};
 use crate::lexer::Lexer;
-use crate::token::Token;
+use crate::token::{Span, Token};
 use program::Program;
 
-use ast::{Expression, Statement};
+use ast::{Expression, ExpressionKind, Statement, StatementKind};
 
 use precedence::Precedence;
 
@@ -17,7 +18,13 @@ pub struct Parser<'a> {
     lexer: Lexer<'a>,
     curr_token: Token,
     next_token: Token,
-    pub errors: Vec<String>,
+    curr_span: Span,
+    next_span: Span,
+    // How many loops the current token is nested in, reset inside of functions
+    loop_depth: usize,
+    // How many blocks the current token is nested in, imports are only allowed at depth 0
+    block_depth: usize,
+    pub errors: Vec<ParserError>,
 }
 
 #[allow(dead_code)]
@@ -27,6 +34,10 @@ impl<'a> Parser<'a> {
             lexer,
             curr_token: token![ILLEGAL],
             next_token: token![ILLEGAL],
+            curr_span: Span::default(),
+            next_span: Span::default(),
+            loop_depth: 0,
+            block_depth: 0,
             errors: Vec::new(),
         };
 
@@ -39,7 +50,13 @@ impl<'a> Parser<'a> {
 
     fn advance_tokens(&mut self) {
         self.curr_token = self.next_token.clone();
-        self.next_token = self.lexer.next_token();
+        self.curr_span = self.next_span;
+
+        (self.next_token, self.next_span) = self.lexer.next_spanned_token();
+
+        // Lexer errors are reported alongside the parser errors in source order
+        let lexer_errors = self.lexer.errors.drain(..).map(ParserError::from);
+        self.errors.extend(lexer_errors);
     }
 
     pub fn parse_program(&mut self) -> Program {
@@ -59,42 +76,64 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_statement(&mut self) -> Option<Statement> {
-        match self.curr_token {
+        let start = self.curr_span;
+
+        let kind = match self.curr_token {
             token![LET] => self.parse_let_statement(),
             token![RETURN] => self.parse_return_statement(),
+            token![WHILE] => self.parse_while_statement(),
+            token![FOR] => self.parse_for_statement(),
+            token![BREAK] => self.parse_loop_control_statement(StatementKind::BreakStatement),
+            token![CONTINUE] => {
+                self.parse_loop_control_statement(StatementKind::ContinueStatement)
+            }
+            token![IMPORT] => self.parse_import_statement(),
             _ => self.parse_expression_statement(),
-        }
+        }?;
+
+        // The statement spans from its first token up to the token we stopped on
+        Some(Statement::new(kind, start.to(self.curr_span)))
     }
 
-    fn parse_expression_statement(&mut self) -> Option<Statement> {
+    fn parse_expression_statement(&mut self) -> Option<StatementKind> {
         if let Some(expr) = self.parse_expression(Precedence::Lowest) {
             if self.is_next_token(Token::Semicolon) {
                 self.advance_tokens();
             }
 
-            return Some(Statement::ExpressionStatement(expr));
+            return Some(StatementKind::ExpressionStatement(expr));
         }
         None
     }
 
     fn parse_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        let start = self.curr_span;
+
         // Get the first expression
         let prefix = self.parse_prefix();
 
         // Something has messed up with other logic if this code runs
         if prefix.is_none() {
-            self.report_error(format!("no prefix parse function for {}", self.curr_token));
+            // Illegal tokens have already been reported by the lexer, and a prefix that
+            // moved past its first token has reported its own error
+            if !self.is_curr_token(token![ILLEGAL]) && self.curr_span == start {
+                self.report_error(
+                    ParserErrorKind::ExpectedExpression(self.curr_token.clone()),
+                    format!("no prefix parse function for {}", self.curr_token),
+                    self.curr_span,
+                );
+            }
             return None;
         }
 
         // We loop through and update the expression as it grow in size
-        let mut left = prefix.unwrap();
+        let mut left = Expression::new(prefix.unwrap(), start.to(self.curr_span));
 
         // Loop until we hit a semicolon or a token with a lower precedence
         while !self.is_next_token(token![;]) && precedence < Precedence::from(&self.next_token) {
             // Get the infix expression
             if let Some(infix) = self.parse_infix(&left) {
-                left = infix;
+                left = Expression::new(infix, left.span.to(self.curr_span));
             } else {
                 return Some(left);
             }
@@ -103,21 +142,28 @@ impl<'a> Parser<'a> {
         Some(left)
     }
 
-    fn parse_prefix(&mut self) -> Option<Expression> {
+    fn parse_prefix(&mut self) -> Option<ExpressionKind> {
         match &self.curr_token {
             Token::Ident(name) => Some(self.parse_identifier(name.clone())),
             Token::Int(i) => Some(self.parse_integer(*i)),
+            Token::BigInt(i) => Some(ExpressionKind::BigIntExpression(i.clone())),
+            Token::Float(f) => Some(ExpressionKind::FloatExpression(*f)),
             Token::String(val) => Some(self.parse_string(val.clone())),
+            Token::TemplateHead(head) => self.parse_template_expression(head.clone()),
             token![TRUE] | token![FALSE] => Some(self.parse_boolean_expression()),
             token![!] | token![-] => self.parse_prefix_expression(),
             token!['('] => self.parse_grouped_expression(),
             token![IF] => self.parse_if_expression(),
             token![FN] => self.parse_function_expression(),
+            token!['['] => self.parse_array_expression(),
+            // Block statements are only parsed after `if`, `else` and `fn` so a '{' in
+            // expression position is always the start of a hash literal
+            token!['{'] => self.parse_hash_expression(),
             _ => None,
         }
     }
 
-    fn parse_infix(&mut self, left: &Expression) -> Option<Expression> {
+    fn parse_infix(&mut self, left: &Expression) -> Option<ExpressionKind> {
         // Advance the tokens only if we have a valid infix operator
         match &self.next_token {
             token![+]
@@ -125,17 +171,34 @@ impl<'a> Parser<'a> {
             | token![*]
             | token![/]
             | token![%]
+            | token![**]
             | token![==]
             | token![!=]
             | token![<]
-            | token![>] => {
+            | token![>]
+            | token![<=]
+            | token![>=]
+            | token![&&]
+            | token![||] => {
                 self.advance_tokens();
                 self.parse_infix_expression(left.clone())
             }
+            token![=] | token![+=] | token![-=] | token![*=] | token![/=] | token![%=] => {
+                self.advance_tokens();
+                self.parse_assign_expression(left)
+            }
             token!['('] => {
                 self.advance_tokens();
                 self.parse_call_expression(left.clone())
             }
+            token!['['] => {
+                self.advance_tokens();
+                self.parse_index_expression(left.clone())
+            }
+            token![.] => {
+                self.advance_tokens();
+                self.parse_member_expression(left.clone())
+            }
             _ => None,
         }
     }
@@ -153,15 +216,42 @@ impl<'a> Parser<'a> {
             return true;
         }
 
-        self.report_error(format!(
-            "expected next token to be {}, got {} instead",
-            token, self.next_token
-        ));
+        // Illegal tokens have already been reported by the lexer
+        if !self.is_next_token(token![ILLEGAL]) {
+            self.report_error(
+                ParserErrorKind::UnexpectedToken {
+                    expected: token.clone(),
+                    found: self.next_token.clone(),
+                },
+                format!(
+                    "expected next token to be {}, got {} instead",
+                    token, self.next_token
+                ),
+                self.next_span,
+            );
+        }
         false
     }
 
-    fn report_error(&mut self, msg: String) {
-        self.errors.push(msg);
+    // For tokens that carry a value, which is described instead of shown
+    fn report_unexpected_next_token(&mut self, expected: Token, description: &str) {
+        if !self.is_next_token(token![ILLEGAL]) {
+            self.report_error(
+                ParserErrorKind::UnexpectedToken {
+                    expected,
+                    found: self.next_token.clone(),
+                },
+                format!(
+                    "expected next token to be {description}, got {} instead",
+                    self.next_token
+                ),
+                self.next_span,
+            );
+        }
+    }
+
+    fn report_error(&mut self, kind: ParserErrorKind, msg: String, span: Span) {
+        self.errors.push(ParserError::new(kind, msg).with_span(span));
     }
 
     fn advance_if_expected(&mut self, token: Token) -> bool {
@@ -172,7 +262,7 @@ impl<'a> Parser<'a> {
         false
     }
 
-    fn parse_let_statement(&mut self) -> Option<Statement> {
+    fn parse_let_statement(&mut self) -> Option<StatementKind> {
         // Make sure we have an identifier after the let keyword
         let identifier = match &self.next_token {
             Token::Ident(_s) => {
@@ -195,7 +285,7 @@ impl<'a> Parser<'a> {
                 self.advance_tokens();
             }
 
-            return Some(Statement::LetStatement {
+            return Some(StatementKind::LetStatement {
                 ident: identifier,
                 value,
             });
@@ -203,7 +293,7 @@ impl<'a> Parser<'a> {
         None
     }
 
-    fn parse_return_statement(&mut self) -> Option<Statement> {
+    fn parse_return_statement(&mut self) -> Option<StatementKind> {
         // Advance past the return keyword
         self.advance_tokens();
 
@@ -212,31 +302,155 @@ impl<'a> Parser<'a> {
             if self.is_next_token(token![;]) {
                 self.advance_tokens();
             }
-            return Some(Statement::ReturnStatement(value));
+            return Some(StatementKind::ReturnStatement(value));
         }
         None
     }
 
-    fn parse_identifier(&self, name: String) -> Expression {
-        Expression::IdentifierExpression(name)
+    fn parse_while_statement(&mut self) -> Option<StatementKind> {
+        // Advance past the while keyword
+        self.advance_tokens();
+
+        let condition = self.parse_expression(Precedence::Lowest)?;
+
+        if !self.advance_if_expected(token!['{']) {
+            return None;
+        }
+
+        self.loop_depth += 1;
+        let body = self.parse_block_statement();
+        self.loop_depth -= 1;
+
+        if self.is_next_token(token![;]) {
+            self.advance_tokens();
+        }
+
+        Some(StatementKind::WhileStatement {
+            condition,
+            body: Box::new(body),
+        })
+    }
+
+    // Parses `for (x in xs) { ... }`, the parentheses are optional like in `while`
+    fn parse_for_statement(&mut self) -> Option<StatementKind> {
+        let parenthesized = self.is_next_token(token!['(']);
+        if parenthesized {
+            self.advance_tokens();
+        }
+
+        let ident = match &self.next_token {
+            Token::Ident(_) => {
+                self.advance_tokens();
+                self.curr_token.clone()
+            }
+            _ => {
+                self.expect_next_token(token![IDENT("identifier")]);
+                return None;
+            }
+        };
+
+        if !self.advance_if_expected(token![IN]) {
+            return None;
+        }
+
+        // Advance past the in keyword
+        self.advance_tokens();
+
+        let iterable = self.parse_expression(Precedence::Lowest)?;
+
+        if parenthesized && !self.advance_if_expected(token![')']) {
+            return None;
+        }
+
+        if !self.advance_if_expected(token!['{']) {
+            return None;
+        }
+
+        self.loop_depth += 1;
+        let body = self.parse_block_statement();
+        self.loop_depth -= 1;
+
+        if self.is_next_token(token![;]) {
+            self.advance_tokens();
+        }
+
+        Some(StatementKind::ForStatement {
+            ident,
+            iterable,
+            body: Box::new(body),
+        })
+    }
+
+    // Parses `break` and `continue`, which are only allowed inside of a loop
+    fn parse_loop_control_statement(&mut self, kind: StatementKind) -> Option<StatementKind> {
+        if self.loop_depth == 0 {
+            self.report_error(
+                ParserErrorKind::OutsideLoop(self.curr_token.clone()),
+                format!("{} outside of a loop", self.curr_token),
+                self.curr_span,
+            );
+        }
+
+        if self.is_next_token(token![;]) {
+            self.advance_tokens();
+        }
+
+        Some(kind)
+    }
+
+    // Parses `import "path" as name;`, which is only allowed at the top level of a file
+    fn parse_import_statement(&mut self) -> Option<StatementKind> {
+        if self.block_depth > 0 {
+            self.report_error(
+                ParserErrorKind::NestedImport,
+                "import is only allowed at the top level of a file".to_string(),
+                self.curr_span,
+            );
+        }
+
+        let Token::String(path) = self.next_token.clone() else {
+            self.report_unexpected_next_token(token![STR(String::new())], "a module path");
+            return None;
+        };
+        self.advance_tokens();
+
+        if !self.advance_if_expected(token![AS]) {
+            return None;
+        }
+
+        let Token::Ident(ident) = self.next_token.clone() else {
+            self.report_unexpected_next_token(token![IDENT("")], "an identifier");
+            return None;
+        };
+        self.advance_tokens();
+
+        if self.is_next_token(token![;]) {
+            self.advance_tokens();
+        }
+
+        Some(StatementKind::ImportStatement { path, ident })
+    }
+
+    fn parse_identifier(&self, name: String) -> ExpressionKind {
+        ExpressionKind::IdentifierExpression(name)
     }
 
-    fn parse_integer(&self, value: i64) -> Expression {
-        Expression::IntExpression(value)
+    fn parse_integer(&self, value: i64) -> ExpressionKind {
+        ExpressionKind::IntExpression(value)
     }
 
-    fn parse_string(&self, value: String) -> Expression {
-        Expression::StringExpression(value)
+    fn parse_string(&self, value: String) -> ExpressionKind {
+        ExpressionKind::StringExpression(value)
     }
 
     // This function is called when we have an operator and an expression after it
-    fn parse_prefix_expression(&mut self) -> Option<Expression> {
+    fn parse_prefix_expression(&mut self) -> Option<ExpressionKind> {
         let op_token = self.curr_token.clone();
 
         self.advance_tokens();
 
         if let Some(right) = self.parse_expression(Precedence::Prefix) {
-            return Some(Expression::PrefixExpression {
+            return Some(ExpressionKind::PrefixExpression {
                 op_token,
                 right: Box::new(right),
             });
@@ -245,14 +459,20 @@ impl<'a> Parser<'a> {
     }
 
     // This function is called when we have a left expression and a right expression and an operator in between them
-    fn parse_infix_expression(&mut self, left: Expression) -> Option<Expression> {
+    fn parse_infix_expression(&mut self, left: Expression) -> Option<ExpressionKind> {
         let op_token = self.curr_token.clone();
-        let curr_precedence = Precedence::from(&op_token);
+        let mut curr_precedence = Precedence::from(&op_token);
+
+        // `**` is right associative, the right operand takes any following `**` with it so
+        // `2 ** 3 ** 2` is `2 ** (3 ** 2)`
+        if op_token == token![**] {
+            curr_precedence = Precedence::Prefix;
+        }
 
         self.advance_tokens();
 
         if let Some(right) = self.parse_expression(curr_precedence) {
-            return Some(Expression::InfixExpression {
+            return Some(ExpressionKind::InfixExpression {
                 left: Box::new(left),
                 op_token,
                 right: Box::new(right),
@@ -261,11 +481,35 @@ impl<'a> Parser<'a> {
         None
     }
 
-    fn parse_boolean_expression(&self) -> Expression {
-        Expression::BooleanExpression(self.is_curr_token(token![TRUE]))
+    fn parse_assign_expression(&mut self, target: &Expression) -> Option<ExpressionKind> {
+        let op_token = self.curr_token.clone();
+
+        let ExpressionKind::IdentifierExpression(name) = &target.kind else {
+            self.report_error(
+                ParserErrorKind::InvalidAssignmentTarget,
+                format!("cannot assign to {target}"),
+                target.span,
+            );
+            return None;
+        };
+
+        self.advance_tokens();
+
+        // Assignment is right associative so `a = b = 1` assigns 1 to both
+        let value = self.parse_expression(Precedence::Lowest)?;
+
+        Some(ExpressionKind::AssignExpression {
+            name: name.clone(),
+            op_token,
+            value: Box::new(value),
+        })
     }
 
-    fn parse_grouped_expression(&mut self) -> Option<Expression> {
+    fn parse_boolean_expression(&self) -> ExpressionKind {
+        ExpressionKind::BooleanExpression(self.is_curr_token(token![TRUE]))
+    }
+
+    fn parse_grouped_expression(&mut self) -> Option<ExpressionKind> {
         self.advance_tokens();
 
         let expr = self.parse_expression(Precedence::Lowest);
@@ -275,10 +519,11 @@ impl<'a> Parser<'a> {
             return None;
         }
 
-        expr
+        // The span is widened by parse_expression to include the parentheses
+        expr.map(|expr| expr.kind)
     }
 
-    fn parse_if_expression(&mut self) -> Option<Expression> {
+    fn parse_if_expression(&mut self) -> Option<ExpressionKind> {
         self.advance_tokens();
 
         let condition = self.parse_expression(Precedence::Lowest);
@@ -305,14 +550,14 @@ impl<'a> Parser<'a> {
             //     self.advance_tokens();
             // }
 
-            return Some(Expression::IfExpression {
+            return Some(ExpressionKind::IfExpression {
                 condition: Box::new(condition.unwrap()),
                 consequence: Box::new(consequence),
                 alternative: Some(Box::new(alternative)),
             });
         }
 
-        Some(Expression::IfExpression {
+        Some(ExpressionKind::IfExpression {
             condition: Box::new(condition.unwrap()),
             consequence: Box::new(consequence),
             alternative: None,
@@ -320,9 +565,12 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_block_statement(&mut self) -> Statement {
+        let start = self.curr_span;
+
         self.advance_tokens();
         let mut statements = Vec::new();
 
+        self.block_depth += 1;
         while !self.is_curr_token(token!['}']) && !self.is_curr_token(token![EOF]) {
             if let Some(stmt) = self.parse_statement() {
                 statements.push(stmt);
@@ -330,11 +578,15 @@ impl<'a> Parser<'a> {
 
             self.advance_tokens();
         }
+        self.block_depth -= 1;
 
-        Statement::BlockStatement(statements)
+        Statement::new(
+            StatementKind::BlockStatement(statements),
+            start.to(self.curr_span),
+        )
     }
 
-    fn parse_function_expression(&mut self) -> Option<Expression> {
+    fn parse_function_expression(&mut self) -> Option<ExpressionKind> {
         if !self.advance_if_expected(token!['(']) {
             return None;
         }
@@ -345,9 +597,12 @@ impl<'a> Parser<'a> {
             return None;
         }
 
+        // A loop outside of the function cannot be controlled from inside of it
+        let loop_depth = std::mem::take(&mut self.loop_depth);
         let body = self.parse_block_statement();
+        self.loop_depth = loop_depth;
 
-        Some(Expression::FunctionExpression {
+        Some(ExpressionKind::FunctionExpression {
             parameters: parameters.unwrap(),
             body: Box::new(body),
         })
@@ -369,10 +624,14 @@ impl<'a> Parser<'a> {
             Token::Ident(name) => {
                 identifiers.push(name.clone());
             }
-            _ => self.report_error(format!(
-                "Failure in parse_function_parameters. expected Token::Ident, got {} instead",
-                self.curr_token
-            )),
+            _ => self.report_error(
+                ParserErrorKind::InvalidParameter(self.curr_token.clone()),
+                format!(
+                    "Failure in parse_function_parameters. expected Token::Ident, got {} instead",
+                    self.curr_token
+                ),
+                self.curr_span,
+            ),
         }
 
         while self.is_next_token(token![,]) {
@@ -384,10 +643,14 @@ impl<'a> Parser<'a> {
                 Token::Ident(name) => {
                     identifiers.push(name.clone());
                 }
-                _ => self.report_error(format!(
-                    "Failure in parse_function_parameters. expected Token::Ident, got {} instead",
-                    self.curr_token
-                )),
+                _ => self.report_error(
+                    ParserErrorKind::InvalidParameter(self.curr_token.clone()),
+                    format!(
+                        "Failure in parse_function_parameters. expected Token::Ident, got {} instead",
+                        self.curr_token
+                    ),
+                    self.curr_span,
+                ),
             }
         }
 
@@ -398,39 +661,141 @@ impl<'a> Parser<'a> {
         Some(identifiers)
     }
 
-    fn parse_call_expression(&mut self, function: Expression) -> Option<Expression> {
-        let arguments = self.parse_call_arguments();
+    fn parse_call_expression(&mut self, function: Expression) -> Option<ExpressionKind> {
+        let arguments = self.parse_expression_list(token![')']);
 
-        Some(Expression::CallExpression {
+        Some(ExpressionKind::CallExpression {
             function: Box::new(function),
             arguments: arguments.unwrap_or_default(),
         })
     }
 
-    fn parse_call_arguments(&mut self) -> Option<Vec<Expression>> {
-        let mut arguments = Vec::new();
+    fn parse_array_expression(&mut self) -> Option<ExpressionKind> {
+        let elements = self.parse_expression_list(token![']'])?;
 
-        if self.is_next_token(token![')']) {
+        Some(ExpressionKind::ArrayExpression(elements))
+    }
+
+    fn parse_index_expression(&mut self, left: Expression) -> Option<ExpressionKind> {
+        // Advance past the '['
+        self.advance_tokens();
+
+        let index = self.parse_expression(Precedence::Lowest)?;
+
+        if !self.advance_if_expected(token![']']) {
+            return None;
+        }
+
+        Some(ExpressionKind::IndexExpression {
+            left: Box::new(left),
+            index: Box::new(index),
+        })
+    }
+
+    fn parse_member_expression(&mut self, left: Expression) -> Option<ExpressionKind> {
+        let Token::Ident(name) = self.next_token.clone() else {
+            self.report_unexpected_next_token(token![IDENT("")], "an identifier");
+            return None;
+        };
+        self.advance_tokens();
+
+        Some(ExpressionKind::MemberExpression {
+            left: Box::new(left),
+            name,
+        })
+    }
+
+    // The text and expressions of `f"a {x} b"` become the parts "a ", x and " b"
+    fn parse_template_expression(&mut self, head: String) -> Option<ExpressionKind> {
+        let mut parts = vec![Expression::new(
+            ExpressionKind::StringExpression(head),
+            self.curr_span,
+        )];
+
+        loop {
+            // Move the expression into curr_token
+            self.advance_tokens();
+
+            parts.push(self.parse_expression(Precedence::Lowest)?);
+
+            let (text, done) = match &self.next_token {
+                Token::TemplateMiddle(text) => (text.clone(), false),
+                Token::TemplateTail(text) => (text.clone(), true),
+                _ => {
+                    self.expect_next_token(token![TEMPLATE_TAIL(String::new())]);
+                    return None;
+                }
+            };
+
+            self.advance_tokens();
+            parts.push(Expression::new(
+                ExpressionKind::StringExpression(text),
+                self.curr_span,
+            ));
+
+            if done {
+                return Some(ExpressionKind::TemplateExpression(parts));
+            }
+        }
+    }
+
+    fn parse_hash_expression(&mut self) -> Option<ExpressionKind> {
+        let mut pairs = Vec::new();
+
+        while !self.is_next_token(token!['}']) {
+            // Move the key into curr_token
+            self.advance_tokens();
+
+            let key = self.parse_expression(Precedence::Lowest)?;
+
+            if !self.advance_if_expected(token![:]) {
+                return None;
+            }
+
+            // Move the value into curr_token
+            self.advance_tokens();
+
+            let value = self.parse_expression(Precedence::Lowest)?;
+
+            pairs.push((key, value));
+
+            if !self.is_next_token(token!['}']) && !self.advance_if_expected(token![,]) {
+                return None;
+            }
+        }
+
+        if !self.advance_if_expected(token!['}']) {
+            return None;
+        }
+
+        Some(ExpressionKind::HashExpression(pairs))
+    }
+
+    // Parses a comma separated list of expressions up to and including the `end` token
+    fn parse_expression_list(&mut self, end: Token) -> Option<Vec<Expression>> {
+        let mut list = Vec::new();
+
+        if self.is_next_token(end.clone()) {
             self.advance_tokens();
-            return Some(arguments);
+            return Some(list);
         }
 
-        // Move the first argument into curr_token
+        // Move the first expression into curr_token
         self.advance_tokens();
 
-        // Add the first argument to the list
-        arguments.push(self.parse_expression(Precedence::Lowest).unwrap());
+        // Add the first expression to the list
+        list.push(self.parse_expression(Precedence::Lowest)?);
 
         while self.is_next_token(token![,]) {
             self.advance_tokens();
             self.advance_tokens();
-            arguments.push(self.parse_expression(Precedence::Lowest).unwrap());
+            list.push(self.parse_expression(Precedence::Lowest)?);
         }
 
-        if !self.advance_if_expected(token![')']) {
+        if !self.advance_if_expected(end) {
             return None;
         }
 
-        Some(arguments)
+        Some(list)
     }
 }
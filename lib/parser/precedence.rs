@@ -3,22 +3,34 @@ use crate::token::Token;
 #[derive(Debug, PartialEq, PartialOrd)]
 pub enum Precedence {
     Lowest,
+    Assign,      // = or +=
+    Or,          // ||
+    And,         // &&
     Equality,    // == or !=
-    LessGreater, // < or >
+    LessGreater, // <, >, <= or >=
     AddSubtract, // + or -
     TimesDivideMod, // * or /
     Prefix,      // -X or !X
+    Power,       // ** binds tighter than a prefix, so -2 ** 2 is -(2 ** 2)
     Call,        // myFunction(X)
+    Index,       // array[index] or module.name
 }
 
 impl From<&Token> for Precedence {
     fn from(value: &Token) -> Self {
         match value {
+            token![=] | token![+=] | token![-=] | token![*=] | token![/=] | token![%=] => {
+                Precedence::Assign
+            }
+            token![||] => Precedence::Or,
+            token![&&] => Precedence::And,
             token![==] | token![!=] => Precedence::Equality,
-            token![<] | token![>] => Precedence::LessGreater,
+            token![<] | token![>] | token![<=] | token![>=] => Precedence::LessGreater,
             token![+] | token![-] => Precedence::AddSubtract,
             token![*] | token![/] | token![%] => Precedence::TimesDivideMod,
+            token![**] => Precedence::Power,
             token!['('] => Precedence::Call,
+            token!['['] | token![.] => Precedence::Index,
             _ => Precedence::Lowest,
         }
     }
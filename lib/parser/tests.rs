@@ -1,7 +1,11 @@
-use crate::{lexer::Lexer, token::Token};
+use crate::{
+    error::{LexerErrorKind, ParserErrorKind},
+    lexer::Lexer,
+    token::{Span, Token},
+};
 
 use super::{
-    ast::{Expression, Statement},
+    ast::{Expression, ExpressionKind, Statement, StatementKind},
     program::Program,
     Parser,
 };
@@ -54,8 +58,8 @@ fn test_parse_let_statements() {
 }
 
 fn test_let_statement(smt: &Statement, expected_name: &str, expected_value: &str) {
-    match smt {
-        Statement::LetStatement { ident, value } => match ident {
+    match &smt.kind {
+        StatementKind::LetStatement { ident, value } => match ident {
             Token::Ident(name) => {
                 assert_eq!(expected_name, name, "ident not '{}'. got={}", expected_name, name );
                 assert_eq!(expected_value, value.to_string(), "value not '{}'. got={}", expected_value, value)
@@ -78,8 +82,8 @@ fn test_parse_return_statements() {
 
         let stmt = program.statments.first().unwrap();
 
-        match stmt {
-            Statement::ReturnStatement(return_value) => {
+        match &stmt.kind {
+            StatementKind::ReturnStatement(return_value) => {
                 assert_eq!(expected_value, return_value.to_string(), "return_value not '{}'. got={}", expected_value, return_value)
             },
             _ => panic!("stmt not Statement::ReturnStatement. got={}", stmt),
@@ -94,15 +98,15 @@ fn test_parse_ident_expression() {
     let program = setup_and_validate(input, 1);
 
     let stmt = program.statments.first().unwrap();
-    match stmt {
-        Statement::ExpressionStatement(expr) => test_identifier(expr, "foobar"),
+    match &stmt.kind {
+        StatementKind::ExpressionStatement(expr) => test_identifier(expr, "foobar"),
         _ => panic!("stmt not Statement::ExpressionStatement. got={}", stmt),
     }
 }
 
 fn test_identifier(expr: &Expression, value: &str) {
-    match expr {
-        Expression::IdentifierExpression(ident) => {
+    match &expr.kind {
+        ExpressionKind::IdentifierExpression(ident) => {
             assert_eq!(ident, value, "ident not '{}'. got={}", value, ident)
         }
         _ => panic!("expr not Expression::IdentifierExpression. got={}", expr),
@@ -117,15 +121,15 @@ fn test_parse_integer_expression() {
 
     let stmt = program.statments.first().unwrap();
 
-    match stmt {
-        Statement::ExpressionStatement(expr) => test_integer_expression(expr, &5),
+    match &stmt.kind {
+        StatementKind::ExpressionStatement(expr) => test_integer_expression(expr, &5),
         _ => panic!("stmt not Statement::ExpressionStatement. got={}", stmt),
     }
 }
 
 fn test_integer_expression(expr: &Expression, expect_value: &i64) {
-    match expr {
-        Expression::IntExpression(i) => {
+    match &expr.kind {
+        ExpressionKind::IntExpression(i) => {
             assert_eq!(i, expect_value, "value not '{}'. got={}", expect_value, i)
         }
         _ => panic!("expr not Expression::IntExpression. got={}", expr),
@@ -140,9 +144,9 @@ fn test_parse_prefix_expression() {
 
     let stmt = program.statments.first().unwrap();
 
-    match stmt {
-        Statement::ExpressionStatement(expr) => match expr {
-            Expression::PrefixExpression { op_token, right } => {
+    match &stmt.kind {
+        StatementKind::ExpressionStatement(expr) => match &expr.kind {
+            ExpressionKind::PrefixExpression { op_token, right } => {
                 assert_eq!(
                     op_token,
                     &Token::Minus,
@@ -166,8 +170,8 @@ fn test_parse_infix_expression() {
 
     let stmt = program.statments.first().unwrap();
 
-    match stmt {
-        Statement::ExpressionStatement(expr) => test_infix_expression(expr, "5", "+", "4089"),
+    match &stmt.kind {
+        StatementKind::ExpressionStatement(expr) => test_infix_expression(expr, "5", "+", "4089"),
         _ => panic!("stmt not Statement::ExpressionStatement. got={}", stmt),
     }
 }
@@ -178,8 +182,8 @@ fn test_infix_expression(
     expect_op: &str,
     expect_right: &str,
 ) {
-    match expr {
-        Expression::InfixExpression {
+    match &expr.kind {
+        ExpressionKind::InfixExpression {
             left,
             op_token,
             right,
@@ -232,6 +236,25 @@ fn test_operator_precedence_parsing() {
             "add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))",
             "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))",
         ),
+        (
+            "a * [1, 2, 3, 4][b * c] * d",
+            "((a * ([1, 2, 3, 4][(b * c)])) * d)",
+        ),
+        (
+            "add(a * b[2], b[1], 2 * [1, 2][1])",
+            "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))",
+        ),
+        ("a || b && c", "(a || (b && c))"),
+        ("a && b || c && d", "((a && b) || (c && d))"),
+        ("a == b && c < d", "((a == b) && (c < d))"),
+        ("!a || b", "((!a) || b)"),
+        ("x = a || b", "(x = (a || b))"),
+        ("a + 1 <= b == c >= d * 2", "(((a + 1) <= b) == (c >= (d * 2)))"),
+        // ** is right associative and binds tighter than a prefix operator
+        ("2 ** 3 ** 2", "(2 ** (3 ** 2))"),
+        ("-a ** b * c", "((-(a ** b)) * c)"),
+        ("a ** -b ** c", "(a ** (-(b ** c)))"),
+        ("a ** b[0] ** f(c)", "(a ** ((b[0]) ** f(c)))"),
     ];
 
     for (input, expected) in tests {
@@ -258,8 +281,8 @@ fn test_operator_precedence_parsing() {
 }
 
 fn test_boolean_expression(expr: &Expression, expect_value: &bool) {
-    match expr {
-        Expression::BooleanExpression(b) => {
+    match &expr.kind {
+        ExpressionKind::BooleanExpression(b) => {
             assert_eq!(b, expect_value, "value not '{}'. got={}", expect_value, b)
         }
         _ => panic!("expr not Expression::BooleanExpression. got={}", expr),
@@ -275,8 +298,8 @@ fn test_parse_boolean_expression() {
 
         let stmt = program.statments.first().unwrap();
 
-        match stmt {
-            Statement::ExpressionStatement(expr) => test_boolean_expression(expr, &expected),
+        match &stmt.kind {
+            StatementKind::ExpressionStatement(expr) => test_boolean_expression(expr, &expected),
             _ => panic!("stmt not Statement::ExpressionStatement. got={}", stmt),
         }
     }
@@ -295,8 +318,8 @@ fn test_parse_infix_boolean_expressions() {
 
         let stmt = program.statments.first().unwrap();
 
-        match stmt {
-            Statement::ExpressionStatement(expr) => {
+        match &stmt.kind {
+            StatementKind::ExpressionStatement(expr) => {
                 test_infix_expression(expr, &first.to_string(), op, &second.to_string())
             }
             _ => panic!("stmt not Statement::ExpressionStatement. got={}", stmt),
@@ -305,8 +328,8 @@ fn test_parse_infix_boolean_expressions() {
 }
 
 fn test_block_statements(stmt: &Statement, expect_len: usize, expect: Vec<&str>) {
-    match stmt {
-        Statement::BlockStatement(statements) => {
+    match &stmt.kind {
+        StatementKind::BlockStatement(statements) => {
             assert_eq!(
                 statements.len(),
                 expect_len,
@@ -332,16 +355,16 @@ fn test_parse_if_expression() {
 
     let stmt = program.statments.first().unwrap();
 
-    match stmt {
-        Statement::ExpressionStatement(expr) => match expr {
-            Expression::IfExpression {
+    match &stmt.kind {
+        StatementKind::ExpressionStatement(expr) => match &expr.kind {
+            ExpressionKind::IfExpression {
                 condition,
                 consequence,
                 alternative,
             } => {
                 test_infix_expression(condition, "x", "<", "y");
 
-                test_block_statements(&consequence, 1, vec!["x"]);
+                test_block_statements(consequence, 1, vec!["x"]);
 
                 assert_eq!(
                     alternative, &None,
@@ -363,16 +386,16 @@ fn test_parse_if_else_expression() {
 
     let stmt = program.statments.first().unwrap();
 
-    match stmt {
-        Statement::ExpressionStatement(expr) => match expr {
-            Expression::IfExpression {
+    match &stmt.kind {
+        StatementKind::ExpressionStatement(expr) => match &expr.kind {
+            ExpressionKind::IfExpression {
                 condition,
                 consequence,
                 alternative,
             } => {
                 test_identifier(condition, "x");
 
-                test_block_statements(&consequence, 1, vec!["x"]);
+                test_block_statements(consequence, 1, vec!["x"]);
 
                 match alternative {
                     Some(alt) => test_block_statements(alt, 1, vec!["y"]),
@@ -393,9 +416,9 @@ fn test_parse_function_expression() {
 
     let stmt = program.statments.first().unwrap();
 
-    match stmt {
-        Statement::ExpressionStatement(expr) => match expr {
-            Expression::FunctionExpression { parameters, body } => {
+    match &stmt.kind {
+        StatementKind::ExpressionStatement(expr) => match &expr.kind {
+            ExpressionKind::FunctionExpression { parameters, body } => {
                 assert_eq!(
                     parameters.len(),
                     2,
@@ -422,9 +445,9 @@ fn test_parse_function_call() {
 
     let stmt = program.statments.first().unwrap();
 
-    match stmt {
-        Statement::ExpressionStatement(expr) => match expr {
-            Expression::CallExpression {
+    match &stmt.kind {
+        StatementKind::ExpressionStatement(expr) => match &expr.kind {
+            ExpressionKind::CallExpression {
                 function,
                 arguments,
             } => {
@@ -455,9 +478,9 @@ fn test_parse_string_expression() {
 
     let stmt = program.statments.first().unwrap();
 
-    match stmt {
-        Statement::ExpressionStatement(expr) => match expr {
-            Expression::StringExpression(s) => {
+    match &stmt.kind {
+        StatementKind::ExpressionStatement(expr) => match &expr.kind {
+            ExpressionKind::StringExpression(s) => {
                 assert_eq!(s, "hello world", "s not '{}'. got={}", "hello world", s)
             }
             _ => panic!("expr not Expression::CallExpression. got={}", expr),
@@ -465,3 +488,394 @@ fn test_parse_string_expression() {
         _ => panic!("stmt not Statement::ExpressionStatement. got={}", stmt),
     }
 }
+
+#[test]
+fn test_parse_array_expression() {
+    let input = "[1, 2 * 2, 3 + 3]";
+
+    let program = setup_and_validate(input, 1);
+
+    let stmt = program.statments.first().unwrap();
+
+    match &stmt.kind {
+        StatementKind::ExpressionStatement(expr) => match &expr.kind {
+            ExpressionKind::ArrayExpression(elements) => {
+                assert_eq!(
+                    elements.len(),
+                    3,
+                    "elements does not contain 3 elements. got={:?}",
+                    elements
+                );
+
+                test_integer_expression(&elements[0], &1);
+                test_infix_expression(&elements[1], "2", "*", "2");
+                test_infix_expression(&elements[2], "3", "+", "3");
+            }
+            _ => panic!("expr not Expression::ArrayExpression. got={}", expr),
+        },
+        _ => panic!("stmt not Statement::ExpressionStatement. got={}", stmt),
+    }
+}
+
+#[test]
+fn test_parse_index_expression() {
+    let input = "myArray[1 + 1]";
+
+    let program = setup_and_validate(input, 1);
+
+    let stmt = program.statments.first().unwrap();
+
+    match &stmt.kind {
+        StatementKind::ExpressionStatement(expr) => match &expr.kind {
+            ExpressionKind::IndexExpression { left, index } => {
+                test_identifier(left, "myArray");
+                test_infix_expression(index, "1", "+", "1");
+            }
+            _ => panic!("expr not Expression::IndexExpression. got={}", expr),
+        },
+        _ => panic!("stmt not Statement::ExpressionStatement. got={}", stmt),
+    }
+}
+
+#[test]
+fn test_parse_hash_expression() {
+    let tests = vec![
+        ("{}", "{}"),
+        (r#"{"one": 1, "two": 2}"#, "{one: 1, two: 2}"),
+        (r#"{"one": 0 + 1, true: 10 - 8, 3: 15 / 5}"#, "{one: (0 + 1), true: (10 - 8), 3: (15 / 5)}"),
+    ];
+
+    for (input, expected) in tests {
+        let program = setup_and_validate(input, 1);
+
+        let stmt = program.statments.first().unwrap();
+
+        match &stmt.kind {
+            StatementKind::ExpressionStatement(expr) => match &expr.kind {
+                ExpressionKind::HashExpression(_) => {
+                    assert_eq!(expected, expr.to_string(), "hash not '{}'. got={}", expected, expr)
+                }
+                _ => panic!("expr not Expression::HashExpression. got={}", expr),
+            },
+            _ => panic!("stmt not Statement::ExpressionStatement. got={}", stmt),
+        }
+    }
+}
+
+#[test]
+fn test_parse_spans() {
+    let input = "let x = 1;\nadd(x, 2 * 3);";
+
+    let program = setup_and_validate(input, 2);
+
+    let let_stmt = &program.statments[0];
+    assert_eq!(let_stmt.span, Span { start: 0, end: 10, line: 1, column: 1 });
+
+    let call_stmt = &program.statments[1];
+    assert_eq!(call_stmt.span, Span { start: 11, end: 25, line: 2, column: 1 });
+
+    match &call_stmt.kind {
+        StatementKind::ExpressionStatement(expr) => match &expr.kind {
+            ExpressionKind::CallExpression { function, arguments } => {
+                assert_eq!(expr.span, Span { start: 11, end: 24, line: 2, column: 1 });
+                assert_eq!(function.span, Span { start: 11, end: 14, line: 2, column: 1 });
+                assert_eq!(arguments[0].span, Span { start: 15, end: 16, line: 2, column: 5 });
+                assert_eq!(arguments[1].span, Span { start: 18, end: 23, line: 2, column: 8 });
+            }
+            _ => panic!("expr not ExpressionKind::CallExpression. got={}", expr),
+        },
+        _ => panic!("stmt not StatementKind::ExpressionStatement. got={}", call_stmt),
+    }
+}
+
+#[test]
+fn test_parse_error_location() {
+    let input = "let x = 1;\nlet = 5;";
+
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+    parser.parse_program();
+
+    let error = parser.errors.first().expect("parser has no errors");
+
+    assert_eq!(error.kind, ParserErrorKind::ExpectedExpression(token![=]));
+    assert_eq!(error.span, Some(Span { start: 15, end: 16, line: 2, column: 5 }));
+    assert_eq!(error.to_string(), "2:5: no prefix parse function for =");
+}
+
+#[test]
+fn test_parse_lexer_errors() {
+    let input = "let x = 5 # 3;";
+
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+    parser.parse_program();
+
+    assert_eq!(parser.errors.len(), 1, "unexpected parser errors. got={:?}", parser.errors);
+
+    let error = &parser.errors[0];
+    assert_eq!(error.kind, ParserErrorKind::Lexer(LexerErrorKind::IllegalCharacter));
+    assert_eq!(error.to_string(), "1:11: illegal character '#'");
+}
+
+#[test]
+fn test_parse_unterminated_string() {
+    let input = "let x = \"abc;\nlet y = 2;";
+
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+    parser.parse_program();
+
+    assert_eq!(parser.errors.len(), 1, "unexpected parser errors. got={:?}", parser.errors);
+
+    let error = &parser.errors[0];
+    assert_eq!(error.kind, ParserErrorKind::Lexer(LexerErrorKind::UnterminatedString));
+    assert_eq!(
+        error.to_string(),
+        "1:9: unterminated string literal\n\tnote: the string starts here but is never closed"
+    );
+}
+
+#[test]
+fn test_parse_while_statement() {
+    let input = "while (x < 10) { break; continue; }";
+
+    let program = setup_and_validate(input, 1);
+
+    let stmt = program.statments.first().unwrap();
+
+    match &stmt.kind {
+        StatementKind::WhileStatement { condition, body } => {
+            assert_eq!(condition.to_string(), "(x < 10)");
+            test_block_statements(body, 2, vec!["break;", "continue;"]);
+        }
+        _ => panic!("stmt not StatementKind::WhileStatement. got={}", stmt),
+    }
+}
+
+#[test]
+fn test_parse_loop_control_outside_loop() {
+    let tests = vec![
+        ("break;", token![BREAK], "1:1: break outside of a loop"),
+        (
+            "while (true) { fn() { continue; } }",
+            token![CONTINUE],
+            "1:23: continue outside of a loop",
+        ),
+    ];
+
+    for (input, expected_token, expected_message) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        parser.parse_program();
+
+        assert_eq!(parser.errors.len(), 1, "unexpected parser errors. got={:?}", parser.errors);
+
+        let error = &parser.errors[0];
+        assert_eq!(error.kind, ParserErrorKind::OutsideLoop(expected_token));
+        assert_eq!(error.to_string(), expected_message);
+    }
+}
+
+#[test]
+fn test_parse_for_statement() {
+    let tests = vec![
+        "for (x in xs) { print(x); break; }",
+        "for x in xs { print(x); break; }",
+    ];
+
+    for input in tests {
+        let program = setup_and_validate(input, 1);
+
+        let stmt = program.statments.first().unwrap();
+
+        match &stmt.kind {
+            StatementKind::ForStatement {
+                ident,
+                iterable,
+                body,
+            } => {
+                assert_eq!(ident, &token![IDENT("x")]);
+                assert_eq!(iterable.to_string(), "xs");
+                test_block_statements(body, 2, vec!["print(x)", "break;"]);
+            }
+            _ => panic!("stmt not StatementKind::ForStatement. got={}", stmt),
+        }
+    }
+}
+
+#[test]
+fn test_parse_template_expression() {
+    let tests = vec![
+        (r#"f"Total: {x + 1} items""#, r#"f"Total: {(x + 1)} items""#, 3),
+        (r#"f"{a}, {{b}} {f(c)}!""#, r#"f"{a}, {{b}} {f(c)}!""#, 5),
+    ];
+
+    for (input, expected, len) in tests {
+        let program = setup_and_validate(input, 1);
+
+        let stmt = program.statments.first().unwrap();
+
+        match &stmt.kind {
+            StatementKind::ExpressionStatement(Expression {
+                kind: ExpressionKind::TemplateExpression(parts),
+                ..
+            }) => {
+                assert_eq!(parts.len(), len, "wrong number of parts. got={:?}", parts);
+                assert_eq!(stmt.to_string(), expected);
+            }
+            _ => panic!("stmt not ExpressionKind::TemplateExpression. got={}", stmt),
+        }
+    }
+}
+
+#[test]
+fn test_parse_template_expression_errors() {
+    let input = r#"f"a {1 2} b""#;
+
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+    parser.parse_program();
+
+    let error = parser.errors.first().expect("expected a parser error");
+    assert_eq!(error.to_string(), "1:8: expected next token to be }\", got 2 instead");
+
+    // Only the lexer's error is reported for an interpolated string that is never closed
+    for input in [r#"f"a {1"#, r#"f"a {1""#] {
+        let mut parser = Parser::new(Lexer::new(input));
+        parser.parse_program();
+
+        assert_eq!(parser.errors.len(), 1, "input: {input}. got={:?}", parser.errors);
+        assert_eq!(parser.errors[0].message, "unterminated string literal");
+    }
+}
+
+#[test]
+fn test_parse_assign_expression() {
+    let tests = vec![
+        ("x = 5;", "(x = 5)"),
+        ("x += 1 * 2;", "(x += (1 * 2))"),
+        ("x -= 1;", "(x -= 1)"),
+        ("x *= 2;", "(x *= 2)"),
+        ("x /= 2;", "(x /= 2)"),
+        ("x %= 2;", "(x %= 2)"),
+        ("a = b = c + 1;", "(a = (b = (c + 1)))"),
+        ("x = y == z;", "(x = (y == z))"),
+    ];
+
+    for (input, expected) in tests {
+        let program = setup_and_validate(input, 1);
+
+        let stmt = program.statments.first().unwrap();
+        match &stmt.kind {
+            StatementKind::ExpressionStatement(expr) => {
+                assert!(
+                    matches!(expr.kind, ExpressionKind::AssignExpression { .. }),
+                    "expr not ExpressionKind::AssignExpression. got={}",
+                    expr
+                );
+                assert_eq!(expr.to_string(), expected);
+            }
+            _ => panic!("stmt not StatementKind::ExpressionStatement. got={}", stmt),
+        }
+    }
+}
+
+#[test]
+fn test_parse_invalid_assignment_target() {
+    let tests = vec![
+        ("1 = 2;", "1:1: cannot assign to 1"),
+        ("a + b = 2;", "1:1: cannot assign to (a + b)"),
+        ("xs[0] += 1;", "1:1: cannot assign to (xs[0])"),
+    ];
+
+    for (input, expected_message) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        parser.parse_program();
+
+        let error = parser.errors.first().expect("parser has no errors");
+        assert_eq!(error.kind, ParserErrorKind::InvalidAssignmentTarget);
+        assert_eq!(error.to_string(), expected_message);
+    }
+}
+
+#[test]
+fn test_parse_float_expression() {
+    let tests = vec![("2.5;", "2.5"), ("1e3 * -0.5;", "(1000.0 * (-0.5))")];
+
+    for (input, expected) in tests {
+        let program = setup_and_validate(input, 1);
+
+        let stmt = program.statments.first().unwrap();
+        assert_eq!(stmt.to_string(), expected);
+    }
+
+    let program = setup_and_validate("3.0", 1);
+    match &program.statments[0].kind {
+        StatementKind::ExpressionStatement(expr) => match &expr.kind {
+            ExpressionKind::FloatExpression(f) => assert_eq!(*f, 3.0),
+            _ => panic!("expr not ExpressionKind::FloatExpression. got={}", expr),
+        },
+        stmt => panic!("stmt not StatementKind::ExpressionStatement. got={}", stmt),
+    }
+}
+
+#[test]
+fn test_parse_import_statement() {
+    let program = setup_and_validate(r#"import "lib/math.mk" as math; math.square(2)"#, 2);
+
+    match &program.statments[0].kind {
+        StatementKind::ImportStatement { path, ident } => {
+            assert_eq!(path, "lib/math.mk");
+            assert_eq!(ident, "math");
+        }
+        stmt => panic!("stmt not StatementKind::ImportStatement. got={}", stmt),
+    }
+
+    assert_eq!(program.to_string(), "import \"lib/math.mk\" as math;\n(math.square)(2)");
+
+    let tests = vec![
+        ("a.b.c", "((a.b).c)"),
+        ("-a.b ** 2", "(-((a.b) ** 2))"),
+        ("a.b[0]", "((a.b)[0])"),
+    ];
+
+    for (input, expected) in tests {
+        let program = setup_and_validate(input, 1);
+        assert_eq!(program.to_string(), expected);
+    }
+}
+
+#[test]
+fn test_parse_import_errors() {
+    let tests = vec![
+        (
+            "import lib as lib;",
+            "1:8: expected next token to be a module path, got lib instead",
+        ),
+        (r#"import "lib.mk";"#, "1:16: expected next token to be as, got ; instead"),
+        (
+            r#"import "lib.mk" as "lib";"#,
+            "1:20: expected next token to be an identifier, got lib instead",
+        ),
+        ("lib.1", "1:5: expected next token to be an identifier, got 1 instead"),
+        (
+            r#"fn() { import "lib.mk" as lib; }"#,
+            "1:8: import is only allowed at the top level of a file",
+        ),
+        (
+            r#"if (true) { import "lib.mk" as lib; }"#,
+            "1:13: import is only allowed at the top level of a file",
+        ),
+    ];
+
+    for (input, expected_message) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        parser.parse_program();
+
+        let error = parser.errors.first().expect("parser has no errors");
+        assert_eq!(error.to_string(), expected_message, "input: {}", input);
+    }
+}
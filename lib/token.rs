@@ -1,8 +1,37 @@
 use std::fmt::Display;
 
+use num_bigint::BigInt;
+
 use crate::token;
 
-#[derive(PartialEq, Debug, Clone, Eq, Hash)]
+// The location of a token or AST node in the source code
+// `start` and `end` are byte offsets while `line` and `column` (both 1-based) point at `start`,
+// the column counts characters so that non-ASCII text does not shift it
+#[derive(PartialEq, Debug, Clone, Copy, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    // Creates a span that starts at `self` and ends where `other` ends
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end,
+            ..self
+        }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
 #[repr(u8)]
 pub enum Token {
     Illegal,
@@ -11,7 +40,18 @@ pub enum Token {
     // Identifiers and literals
     Ident(String),
     Int(i64),
+    // An integer literal too large for an i64
+    BigInt(BigInt),
+    Float(f64),
     String(String),
+    // The text of an interpolated string like `f"a {x} b {y} c"` is split around its
+    // expressions into a head (`a `), middles (` b `) and a tail (` c`)
+    TemplateHead(String),
+    TemplateMiddle(String),
+    TemplateTail(String),
+
+    // The text of a `///` comment, only returned when the lexer is asked for doc comments
+    DocComment(String),
 
     // Operators
     Assign,
@@ -21,21 +61,41 @@ pub enum Token {
     Asterisk,
     Slash,
     Percent,
+    // Exponentiation, `2 ** 3`
+    Power,
 
     LessThan,
     GreaterThan,
+    LessEqual,
+    GreaterEqual,
 
     Equal,
     NotEqual,
 
+    // Logical operators, the right operand is only evaluated when needed
+    And,
+    Or,
+
+    // Compound assignment
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    SlashAssign,
+    PercentAssign,
+
     // Delimeters
     Comma,
     Semicolon,
+    Colon,
+    // Accesses a binding of a module, `lib.name`
+    Dot,
 
     Lparen,
     Rparen,
     Lbrace,
     Rbrace,
+    Lbracket,
+    Rbracket,
 
     // Keywords
     Func,
@@ -45,6 +105,13 @@ pub enum Token {
     If,
     Else,
     Return,
+    While,
+    Break,
+    Continue,
+    For,
+    In,
+    Import,
+    As,
 }
 
 impl Display for Token {
@@ -54,7 +121,14 @@ impl Display for Token {
             token![EOF] => write!(f, "EOF"),
             Token::Ident(value) => write!(f, "{value}"),
             Token::Int(value) => write!(f, "{value}"),
+            Token::BigInt(value) => write!(f, "{value}"),
+            // Debug always includes the fraction or exponent, so 3.0 is not shown as 3
+            Token::Float(value) => write!(f, "{value:?}"),
             Token::String(value) => write!(f, "{value}"),
+            Token::TemplateHead(value) => write!(f, "f\"{value}{{"),
+            Token::TemplateMiddle(value) => write!(f, "}}{value}{{"),
+            Token::TemplateTail(value) => write!(f, "}}{value}\""),
+            Token::DocComment(value) => write!(f, "///{value}"),
             token![=] => write!(f, "="),
             token![+] => write!(f, "+"),
             token![-] => write!(f, "-"),
@@ -62,16 +136,30 @@ impl Display for Token {
             token![*] => write!(f, "*"),
             token![/] => write!(f, "/"),
             token![%] => write!(f, "%"),
+            token![**] => write!(f, "**"),
             token![<] => write!(f, "<"),
             token![>] => write!(f, ">"),
+            token![<=] => write!(f, "<="),
+            token![>=] => write!(f, ">="),
             token![==] => write!(f, "=="),
             token![!=] => write!(f, "!="),
+            token![&&] => write!(f, "&&"),
+            token![||] => write!(f, "||"),
+            token![+=] => write!(f, "+="),
+            token![-=] => write!(f, "-="),
+            token![*=] => write!(f, "*="),
+            token![/=] => write!(f, "/="),
+            token![%=] => write!(f, "%="),
             token![,] => write!(f, ","),
             token![;] => write!(f, ";"),
+            token![:] => write!(f, ":"),
+            token![.] => write!(f, "."),
             token!['('] => write!(f, "("),
             token![')'] => write!(f, ")"),
             token!['{'] => write!(f, "{{"),
             token!['}'] => write!(f, "}}"),
+            token!['['] => write!(f, "["),
+            token![']'] => write!(f, "]"),
             token![FN] => write!(f, "fn"),
             token![LET] => write!(f, "let"),
             token![TRUE] => write!(f, "true"),
@@ -79,6 +167,27 @@ impl Display for Token {
             token![IF] => write!(f, "if"),
             token![ELSE] => write!(f, "else"),
             token![RETURN] => write!(f, "return"),
+            token![WHILE] => write!(f, "while"),
+            token![BREAK] => write!(f, "break"),
+            token![CONTINUE] => write!(f, "continue"),
+            token![FOR] => write!(f, "for"),
+            token![IN] => write!(f, "in"),
+            token![IMPORT] => write!(f, "import"),
+            token![AS] => write!(f, "as"),
+        }
+    }
+}
+
+impl Token {
+    // The operator a compound assignment like `+=` applies before assigning
+    pub fn compound_operator(&self) -> Option<Token> {
+        match self {
+            token![+=] => Some(token![+]),
+            token![-=] => Some(token![-]),
+            token![*=] => Some(token![*]),
+            token![/=] => Some(token![/]),
+            token![%=] => Some(token![%]),
+            _ => None,
         }
     }
 }
@@ -92,6 +201,13 @@ pub fn lookup_identifier(identifier: &str) -> Token {
         "false" => token![FALSE],
         "else" => token![ELSE],
         "return" => token![RETURN],
+        "while" => token![WHILE],
+        "break" => token![BREAK],
+        "continue" => token![CONTINUE],
+        "for" => token![FOR],
+        "in" => token![IN],
+        "import" => token![IMPORT],
+        "as" => token![AS],
         _ => token![IDENT(identifier.to_string())],
     }
 }
@@ -102,7 +218,13 @@ macro_rules! token {
     [EOF] => { $crate::token::Token::Eof };
     [IDENT($val:expr)] => { $crate::token::Token::Ident($val.to_string()) };
     [INT($val:expr)] => { $crate::token::Token::Int($val) };
+    [BIGINT($val:expr)] => { $crate::token::Token::BigInt($val) };
+    [FLOAT($val:expr)] => { $crate::token::Token::Float($val) };
     [STR($val:expr)] => { $crate::token::Token::String($val) };
+    [TEMPLATE_HEAD($val:expr)] => { $crate::token::Token::TemplateHead($val) };
+    [TEMPLATE_MIDDLE($val:expr)] => { $crate::token::Token::TemplateMiddle($val) };
+    [TEMPLATE_TAIL($val:expr)] => { $crate::token::Token::TemplateTail($val) };
+    [DOC($val:expr)] => { $crate::token::Token::DocComment($val.to_string()) };
     [=] => { $crate::token::Token::Assign };
     [+] => { $crate::token::Token::Plus };
     [-] => { $crate::token::Token::Minus };
@@ -110,16 +232,30 @@ macro_rules! token {
     [*] => { $crate::token::Token::Asterisk };
     [/] => { $crate::token::Token::Slash };
     [%] => { $crate::token::Token::Percent };
+    [**] => { $crate::token::Token::Power };
     [<] => { $crate::token::Token::LessThan };
     [>] => { $crate::token::Token::GreaterThan };
+    [<=] => { $crate::token::Token::LessEqual };
+    [>=] => { $crate::token::Token::GreaterEqual };
     [==] => { $crate::token::Token::Equal };
     [!=] => { $crate::token::Token::NotEqual };
+    [&&] => { $crate::token::Token::And };
+    [||] => { $crate::token::Token::Or };
+    [+=] => { $crate::token::Token::PlusAssign };
+    [-=] => { $crate::token::Token::MinusAssign };
+    [*=] => { $crate::token::Token::AsteriskAssign };
+    [/=] => { $crate::token::Token::SlashAssign };
+    [%=] => { $crate::token::Token::PercentAssign };
     [,] => { $crate::token::Token::Comma };
     [;] => { $crate::token::Token::Semicolon };
+    [:] => { $crate::token::Token::Colon };
+    [.] => { $crate::token::Token::Dot };
     ['('] => { $crate::token::Token::Lparen };
     [')'] => { $crate::token::Token::Rparen };
     ['{'] => { $crate::token::Token::Lbrace };
     ['}'] => { $crate::token::Token::Rbrace };
+    ['['] => { $crate::token::Token::Lbracket };
+    [']'] => { $crate::token::Token::Rbracket };
     [FN] => { $crate::token::Token::Func };
     [LET] => { $crate::token::Token::Let };
     [TRUE] => { $crate::token::Token::True };
@@ -127,4 +263,11 @@ macro_rules! token {
     [IF] => { $crate::token::Token::If };
     [ELSE] => { $crate::token::Token::Else };
     [RETURN] => { $crate::token::Token::Return };
+    [WHILE] => { $crate::token::Token::While };
+    [BREAK] => { $crate::token::Token::Break };
+    [CONTINUE] => { $crate::token::Token::Continue };
+    [FOR] => { $crate::token::Token::For };
+    [IN] => { $crate::token::Token::In };
+    [IMPORT] => { $crate::token::Token::Import };
+    [AS] => { $crate::token::Token::As };
 }
\ No newline at end of file
@@ -0,0 +1,66 @@
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+use crate::{compiler::CompiledFunction, eval::value::Value};
+
+// The local variables of a single function call, or of one iteration of a for loop
+//
+// Locals live on the heap rather than on the stack so that closures can keep referring to
+// the variables of the function that created them, like `Environment` does for the evaluator
+pub struct Locals {
+    // `None` until the local has been defined
+    pub slots: RefCell<Vec<Option<Value>>>,
+    pub parent: Option<Rc<Locals>>,
+}
+
+impl Locals {
+    // Walks `depth` scopes outwards from these locals
+    pub fn ancestor(self: &Rc<Self>, depth: usize) -> &Rc<Locals> {
+        let mut locals = self;
+        for _ in 0..depth {
+            locals = locals.parent.as_ref().expect("free variable is out of scope");
+        }
+        locals
+    }
+}
+
+pub struct Closure {
+    pub func: Rc<CompiledFunction>,
+    // The locals of the function the closure was created in, `None` at the top level
+    pub env: Option<Rc<Locals>>,
+}
+
+// Printing the environment could recurse forever since a closure can be stored in its own env
+impl Debug for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Closure").field("func", &self.func).finish()
+    }
+}
+
+pub struct Frame {
+    pub closure: Rc<Closure>,
+    pub ip: usize,
+    // Offset of the instruction being executed, used to locate errors
+    pub op_ip: usize,
+    pub locals: Option<Rc<Locals>>,
+    // The stack length when the function was called, restored when it returns
+    pub base: usize,
+    // The module whose top level is running, which is pushed instead of the return value
+    pub import: Option<usize>,
+}
+
+impl Frame {
+    pub fn new(closure: Rc<Closure>, locals: Option<Rc<Locals>>, base: usize) -> Self {
+        Frame {
+            closure,
+            ip: 0,
+            op_ip: 0,
+            locals,
+            base,
+            import: None,
+        }
+    }
+
+    pub fn locals(&self) -> &Rc<Locals> {
+        self.locals.as_ref().expect("the top level has no locals")
+    }
+}
@@ -0,0 +1,513 @@
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use crate::{
+    compiler::{
+        code::{read_u16, read_u8, Opcode},
+        symbol_table::{Symbol, SymbolScope},
+        Bytecode, Pool,
+    },
+    error::{EvaluatorError, EvaluatorErrorKind},
+    eval::{
+        env::identifier_not_found,
+        funcs::{get_constant, get_function},
+        module::{error_in_module, Module},
+        ops,
+        random::Random,
+        value::{Truth, Value},
+    },
+    token::Token,
+};
+
+use frame::{Closure, Frame, Locals};
+
+pub mod frame;
+
+#[cfg(test)]
+mod tests;
+
+// Deep enough for any sensible recursion while still catching runaway recursion
+const MAX_FRAMES: usize = 100_000;
+
+// Executes the bytecode produced by the `Compiler`
+//
+// Globals are kept between runs so that the REPL can use bindings from previous lines
+#[derive(Default)]
+pub struct Vm {
+    globals: Vec<Option<Value>>,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    // The value of the last expression statement, which is the result of the program
+    last_popped: Value,
+    random: Random,
+    modules: Vec<ModuleState>,
+}
+
+// The globals of a module and the module itself once it has finished running
+#[derive(Default)]
+struct ModuleState {
+    globals: Vec<Option<Value>>,
+    value: Option<Value>,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Vm::default()
+    }
+
+    // Seeds the random builtins so they produce the same numbers on every run
+    pub fn seed(&mut self, seed: u64) {
+        self.random.seed(seed);
+    }
+
+    pub fn run(&mut self, bytecode: &Bytecode) -> Result<Value, EvaluatorError> {
+        let pool = bytecode.pool.borrow();
+        self.globals.resize(pool.global_names.len(), None);
+        self.modules.resize_with(pool.modules.len(), ModuleState::default);
+        self.stack.clear();
+        self.last_popped = Value::Null;
+
+        let main = Closure {
+            func: Rc::clone(&bytecode.main),
+            env: None,
+        };
+        self.frames = vec![Frame::new(Rc::new(main), None, 0)];
+
+        self.execute(&pool)
+    }
+
+    // Calls a function value from outside of a program, `bytecode` has to come from the
+    // compiler that compiled the function since it refers to its constants and globals
+    pub fn call_function(
+        &mut self,
+        bytecode: &Bytecode,
+        func: Value,
+        arguments: Vec<Value>,
+    ) -> Result<Value, EvaluatorError> {
+        self.stack.clear();
+        self.frames.clear();
+
+        let num_args = arguments.len();
+        self.push(func);
+        self.stack.extend(arguments);
+        self.call(num_args)?;
+
+        // Builtins return right away, closures return once their frame is popped
+        if self.frames.is_empty() {
+            return Ok(self.pop());
+        }
+
+        self.execute(&bytecode.pool.borrow())
+    }
+
+    fn execute(&mut self, pool: &Pool) -> Result<Value, EvaluatorError> {
+        loop {
+            match self.step(pool) {
+                Ok(None) => continue,
+                Ok(Some(value)) => return Ok(value),
+                Err(err) => return Err(self.locate_error(pool, err)),
+            }
+        }
+    }
+
+    // Errors are located at the instruction that produced them, or at the code that used the
+    // module when they were raised in a module
+    fn locate_error(&self, pool: &Pool, mut err: EvaluatorError) -> EvaluatorError {
+        for (i, frame) in self.frames.iter().enumerate().rev() {
+            let func = &frame.closure.func;
+            if let Some(span) = func.span_at(frame.op_ip) {
+                err = err.or_span(span);
+            }
+
+            let Some(module) = func.module else {
+                continue;
+            };
+
+            let caller = i.checked_sub(1).map(|i| self.frames[i].closure.func.module);
+            if caller.is_some_and(|caller| caller != Some(module)) {
+                err = error_in_module(&pool.modules[module].path, err);
+            }
+        }
+
+        err
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().expect("vm has no frames")
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("vm has no frames")
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    // Executes a single instruction, returning the result of the program once it has finished
+    fn step(&mut self, pool: &Pool) -> Result<Option<Value>, EvaluatorError> {
+        let frame = self.frame_mut();
+        let instructions = &frame.closure.func.instructions;
+
+        // Only the top level can run off the end, functions always end in a return
+        if frame.ip >= instructions.len() {
+            return Ok(Some(std::mem::take(&mut self.last_popped)));
+        }
+
+        frame.op_ip = frame.ip;
+        let op = Opcode::try_from(instructions[frame.ip]).expect("invalid opcode");
+
+        // Decode the operands up front so that the frame is free to be modified below
+        let mut operands = [0; 2];
+        let mut offset = frame.ip + 1;
+        for (i, width) in op.operand_widths().iter().enumerate() {
+            operands[i] = match width {
+                1 => read_u8(instructions, offset),
+                _ => read_u16(instructions, offset),
+            };
+            offset += width;
+        }
+        frame.ip = offset;
+
+        match op {
+            Opcode::Constant => self.push(pool.constants[operands[0]].clone()),
+            Opcode::Pop => self.last_popped = self.pop(),
+            Opcode::True => self.push(Value::Boolean(true)),
+            Opcode::False => self.push(Value::Boolean(false)),
+            Opcode::Null => self.push(Value::Null),
+            Opcode::Add
+            | Opcode::Sub
+            | Opcode::Mul
+            | Opcode::Div
+            | Opcode::Mod
+            | Opcode::Pow
+            | Opcode::Equal
+            | Opcode::NotEqual
+            | Opcode::LessThan
+            | Opcode::GreaterThan
+            | Opcode::LessEqual
+            | Opcode::GreaterEqual => {
+                let right = self.pop();
+                let left = self.pop();
+
+                self.push(ops::infix(left, &infix_operator(op), right)?);
+            }
+            Opcode::Minus => {
+                let right = self.pop();
+                self.push(ops::prefix(&token![-], right)?);
+            }
+            Opcode::Bang => {
+                let right = self.pop();
+                self.push(ops::prefix(&token![!], right)?);
+            }
+            Opcode::Jump => self.frame_mut().ip = operands[0],
+            Opcode::JumpNotTruthy => {
+                if !self.pop().truth() {
+                    self.frame_mut().ip = operands[0];
+                }
+            }
+            Opcode::Iter => {
+                let iterable = self.pop();
+                let iter = ops::iterate(iterable)?;
+                self.push(Value::Iterator(Rc::new(RefCell::new(iter))));
+            }
+            Opcode::IterNext => {
+                let next = match self.stack.last() {
+                    Some(Value::Iterator(iter)) => iter.borrow_mut().next(),
+                    _ => unreachable!("for loops keep their iterator on top of the stack"),
+                };
+
+                match next {
+                    Some(value) => self.push(value),
+                    None => self.frame_mut().ip = operands[0],
+                }
+            }
+            Opcode::GetGlobal => match self.globals()[operands[0]].clone() {
+                Some(value) => self.push(value),
+                None => return Err(self.global_not_found(pool, operands[0])),
+            },
+            Opcode::SetGlobal => {
+                let value = self.pop();
+                self.globals()[operands[0]] = Some(value.clone());
+
+                // Like the evaluator, a let statement evaluates to the bound value
+                self.last_popped = value;
+            }
+            Opcode::AssignGlobal => {
+                let value = self.pop();
+
+                match &mut self.globals()[operands[0]] {
+                    Some(global) => *global = value,
+                    None => return Err(self.global_not_found(pool, operands[0])),
+                }
+            }
+            // A local read before it was defined can only come from a let in a branch that
+            // did not run, which leaves it null
+            Opcode::GetLocal => {
+                let value = self.frame().locals().slots.borrow()[operands[0]].clone();
+                self.push(value.unwrap_or(Value::Null));
+            }
+            Opcode::SetLocal => {
+                let value = self.pop();
+                self.frame().locals().slots.borrow_mut()[operands[0]] = Some(value);
+            }
+            Opcode::GetFree => {
+                let locals = self.frame().locals().ancestor(operands[0]);
+                let value = locals.slots.borrow()[operands[1]].clone();
+                self.push(value.unwrap_or(Value::Null));
+            }
+            Opcode::SetFree => {
+                let value = self.pop();
+                let locals = self.frame().locals().ancestor(operands[0]);
+                locals.slots.borrow_mut()[operands[1]] = Some(value);
+            }
+            Opcode::GetName => {
+                let lookup = &pool.lookups[operands[0]];
+
+                let value = lookup
+                    .slots
+                    .iter()
+                    .find_map(|symbol| self.read_symbol(*symbol))
+                    .or_else(|| pool.natives.get(&lookup.name).cloned().map(Value::NativeFunction))
+                    .or_else(|| get_function(&lookup.name).map(|func| Value::BuiltInFunction { func }))
+                    .or_else(|| get_constant(&lookup.name));
+
+                match value {
+                    Some(value) => self.push(value),
+                    None => return Err(identifier_not_found(&lookup.name)),
+                }
+            }
+            Opcode::EnterScope => {
+                let frame = self.frame_mut();
+                let locals = Locals {
+                    slots: RefCell::new(vec![None; operands[0]]),
+                    parent: frame.locals.take(),
+                };
+
+                frame.locals = Some(Rc::new(locals));
+            }
+            Opcode::LeaveScope => {
+                let frame = self.frame_mut();
+                frame.locals = frame.locals.take().and_then(|locals| locals.parent.clone());
+            }
+            Opcode::Array => {
+                let elements = self.stack.split_off(self.stack.len() - operands[0]);
+                self.push(Value::Array(elements));
+            }
+            Opcode::Hash => {
+                let elements = self.stack.split_off(self.stack.len() - operands[0] * 2);
+
+                let mut hash = BTreeMap::new();
+                for pair in elements.chunks(2) {
+                    let Some(key) = pair[0].hash_key() else {
+                        return Err(ops::unusable_hash_key(&pair[0]));
+                    };
+                    hash.insert(key, pair[1].clone());
+                }
+
+                self.push(Value::Hash(hash));
+            }
+            Opcode::Concat => {
+                let parts = self.stack.split_off(self.stack.len() - operands[0]);
+                let output = parts.iter().map(|part| part.to_string()).collect::<String>();
+
+                self.push(Value::String(output));
+            }
+            Opcode::Index => {
+                let index = self.pop();
+                let left = self.pop();
+
+                self.push(ops::index(left, index)?);
+            }
+            Opcode::Member => {
+                let left = self.pop();
+                let name = pool.constants[operands[0]].to_string();
+
+                self.push(ops::member(left, &name)?);
+            }
+            Opcode::Call => self.call(operands[0])?,
+            Opcode::ReturnValue => {
+                let value = self.pop();
+                let frame = self.frames.pop().expect("vm has no frames");
+
+                // Returning from the top level ends the program
+                if self.frames.is_empty() {
+                    return Ok(Some(value));
+                }
+
+                self.stack.truncate(frame.base);
+
+                match frame.import {
+                    Some(index) => {
+                        let module = self.finish_import(pool, index);
+                        self.push(module);
+                    }
+                    None => self.push(value),
+                }
+            }
+            Opcode::Closure => {
+                let closure = Closure {
+                    func: Rc::clone(&pool.functions[operands[0]]),
+                    env: self.frame().locals.clone(),
+                };
+
+                self.push(Value::Closure(Rc::new(closure)));
+            }
+            Opcode::Import => {
+                let index = operands[0];
+
+                if let Some(module) = &self.modules[index].value {
+                    self.push(module.clone());
+                    return Ok(None);
+                }
+
+                let module = &pool.modules[index];
+                self.modules[index]
+                    .globals
+                    .resize(module.global_names.len(), None);
+
+                // The top level of the module runs like a function that returns the module
+                let main = Closure {
+                    func: Rc::clone(&module.main),
+                    env: None,
+                };
+                let mut frame = Frame::new(Rc::new(main), None, self.stack.len());
+                frame.import = Some(index);
+
+                self.frames.push(frame);
+            }
+        }
+
+        Ok(None)
+    }
+
+    // The globals of the module (or main program) the running code was written in
+    fn globals(&mut self) -> &mut Vec<Option<Value>> {
+        match self.frame().closure.func.module {
+            Some(module) => &mut self.modules[module].globals,
+            None => &mut self.globals,
+        }
+    }
+
+    // The value in the slot of the symbol, `None` while it has not been defined
+    fn read_symbol(&mut self, symbol: Symbol) -> Option<Value> {
+        let locals = match symbol.scope {
+            SymbolScope::Global => return self.globals()[symbol.index].clone(),
+            SymbolScope::Local => self.frame().locals(),
+            SymbolScope::Free { depth } => self.frame().locals().ancestor(depth),
+        };
+
+        let value = locals.slots.borrow()[symbol.index].clone();
+        value
+    }
+
+    fn global_not_found(&self, pool: &Pool, index: usize) -> EvaluatorError {
+        let names = match self.frame().closure.func.module {
+            Some(module) => &pool.modules[module].global_names,
+            None => &pool.global_names,
+        };
+
+        identifier_not_found(&names[index])
+    }
+
+    // Collects the bindings of a module that has finished running
+    fn finish_import(&mut self, pool: &Pool, index: usize) -> Value {
+        let module = &pool.modules[index];
+        let state = &mut self.modules[index];
+
+        let bindings = module
+            .bindings
+            .iter()
+            .filter_map(|(name, global)| Some((name.clone(), state.globals[*global].clone()?)))
+            .collect();
+
+        let value = Value::Module(Rc::new(Module {
+            path: module.path.clone(),
+            bindings,
+        }));
+        state.value = Some(value.clone());
+
+        value
+    }
+
+    fn call(&mut self, num_args: usize) -> Result<(), EvaluatorError> {
+        let arguments = self.stack.split_off(self.stack.len() - num_args);
+        let callee = self.pop();
+
+        match callee {
+            Value::Closure(closure) => {
+                let func = &closure.func;
+
+                if func.params.len() != num_args {
+                    return Err(EvaluatorError::new(
+                        EvaluatorErrorKind::WrongArgumentCount {
+                            expected: func.params.len(),
+                            given: num_args,
+                        },
+                        format!(
+                            "fn({}) takes {} arguments, {} arguments given",
+                            func.params.join(", "),
+                            func.params.len(),
+                            num_args
+                        ),
+                    ));
+                }
+
+                if self.frames.len() >= MAX_FRAMES {
+                    return Err(EvaluatorError::new(
+                        EvaluatorErrorKind::StackOverflow,
+                        format!("Stack overflow, functions were nested more than {MAX_FRAMES} deep"),
+                    ));
+                }
+
+                let mut slots = arguments.into_iter().map(Some).collect::<Vec<_>>();
+                slots.resize(func.num_locals, None);
+
+                let locals = Locals {
+                    slots: RefCell::new(slots),
+                    parent: closure.env.clone(),
+                };
+
+                let base = self.stack.len();
+                self.frames
+                    .push(Frame::new(closure, Some(Rc::new(locals)), base));
+            }
+            Value::BuiltInFunction { func } => {
+                let result = func.call(&mut self.random, arguments)?;
+                self.push(result);
+            }
+            Value::NativeFunction(native) => {
+                let result = native.call(&arguments)?;
+                self.push(result);
+            }
+            _ => {
+                return Err(EvaluatorError::new(
+                    EvaluatorErrorKind::NotCallable,
+                    format!("Cannot call {callee}, it is not a function"),
+                ))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn infix_operator(op: Opcode) -> Token {
+    match op {
+        Opcode::Add => token![+],
+        Opcode::Sub => token![-],
+        Opcode::Mul => token![*],
+        Opcode::Div => token![/],
+        Opcode::Mod => token![%],
+        Opcode::Pow => token![**],
+        Opcode::Equal => token![==],
+        Opcode::NotEqual => token![!=],
+        Opcode::LessThan => token![<],
+        Opcode::GreaterThan => token![>],
+        Opcode::LessEqual => token![<=],
+        _ => token![>=],
+    }
+}
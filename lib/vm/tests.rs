@@ -0,0 +1,112 @@
+use crate::{
+    compiler::Compiler,
+    error::{EvaluatorError, EvaluatorErrorKind},
+    eval::value::Value,
+    lexer::Lexer,
+    parser::{program::Program, Parser},
+};
+
+use super::Vm;
+
+fn parse(input: &str) -> Program {
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program();
+    assert!(parser.errors.is_empty(), "Parser had errors");
+
+    program
+}
+
+fn run(input: &str) -> Result<Value, EvaluatorError> {
+    let bytecode = Compiler::new().compile(&parse(input))?;
+    Vm::new().run(&bytecode)
+}
+
+fn test_run(input: &str) -> Value {
+    match run(input) {
+        Ok(v) => v,
+        Err(e) => panic!("Error: {}", e),
+    }
+}
+
+#[test]
+fn test_recursive_functions() {
+    let tests = vec![
+        (
+            "let fib = fn(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }; fib(15);",
+            "610",
+        ),
+        (
+            "let wrapper = fn() { let count = fn(n) { if n == 0 { 0 } else { count(n - 1) } }; count(5) }; wrapper();",
+            "0",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_run(input).to_string(), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_deep_recursion() {
+    // Far deeper than the tree-walking evaluator can go on the native stack
+    let input = "let count = fn(n) { if n == 0 { 0 } else { 1 + count(n - 1) } }; count(50000);";
+
+    assert_eq!(test_run(input).to_string(), "50000");
+}
+
+#[test]
+fn test_stack_overflow() {
+    let err = run("let forever = fn() { forever() }; forever();").unwrap_err();
+
+    assert_eq!(err.kind, EvaluatorErrorKind::StackOverflow);
+}
+
+#[test]
+fn test_late_bound_globals() {
+    let input = "let a = fn() { b() }; let b = fn() { 5 }; a();";
+
+    assert_eq!(test_run(input).to_string(), "5");
+}
+
+#[test]
+fn test_closures_capture_variables() {
+    // Closures capture variables, not their values at the time the closure was created
+    let input = "let f = fn() { let x = 1; let g = fn() { x }; let x = 10; g() }; f();";
+
+    assert_eq!(test_run(input).to_string(), "10");
+}
+
+#[test]
+fn test_globals_persist_between_runs() {
+    let mut compiler = Compiler::new();
+    let mut vm = Vm::new();
+
+    let bytecode = compiler.compile(&parse("let x = 5; let add = fn(y) { x + y };")).unwrap();
+    vm.run(&bytecode).unwrap();
+
+    let bytecode = compiler.compile(&parse("add(10)")).unwrap();
+    assert_eq!(vm.run(&bytecode).unwrap().to_string(), "15");
+}
+
+#[test]
+fn test_programs_run_one_after_another() {
+    let mut compiler = Compiler::new();
+    let mut vm = Vm::new();
+    let mut run = |input| vm.run(&compiler.compile(&parse(input))?);
+
+    // Builtins used by an earlier program are still builtins in the next one
+    assert_eq!(run("len([1, 2])").unwrap().to_string(), "2");
+    assert_eq!(run("len([1, 2, 3])").unwrap().to_string(), "3");
+    assert_eq!(run("let f = fn() { len([]) }; f()").unwrap().to_string(), "0");
+
+    // Until a program defines the name
+    assert_eq!(run("let len = fn(x) { 99 }; len([])").unwrap().to_string(), "99");
+    assert_eq!(run("[len([1]), f()]").unwrap().to_string(), "[99, 99]");
+
+    let error = run("missing").unwrap_err();
+    assert_eq!(error.kind, EvaluatorErrorKind::IdentifierNotFound("missing".to_string()));
+    let error = run("missing + 1").unwrap_err();
+    assert_eq!(error.kind, EvaluatorErrorKind::IdentifierNotFound("missing".to_string()));
+}
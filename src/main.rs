@@ -1,35 +1,65 @@
-use std::io::{stdin, stdout, Write};
+use std::io::{stdin, stdout, IsTerminal, Write};
 
-use monkey_lib::{
-    lexer,
-    parser::{self, program::Program},
-    Monkey,
-};
+use monkey_lib::{error::Error, parser::program::Program, Backend, Monkey};
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
-use std::fs;
+use std::{env, fs, path::Path};
 
-use lexer::Lexer;
+// The name shown in diagnostics for code typed into the REPL
+const REPL_FILE_NAME: &str = "<repl>";
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Args {
     /// The location of the monkey file
     file: Option<String>,
+
+    /// How the program is executed
+    #[arg(long, value_enum, default_value_t = BackendArg::Evaluator)]
+    backend: BackendArg,
+
+    /// Seed for the random builtins, the same seed always produces the same numbers
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BackendArg {
+    /// Walk the syntax tree directly
+    Evaluator,
+    /// Compile to bytecode and run it on the virtual machine
+    Vm,
+}
+
+impl From<BackendArg> for Backend {
+    fn from(value: BackendArg) -> Self {
+        match value {
+            BackendArg::Evaluator => Backend::Evaluator,
+            BackendArg::Vm => Backend::Vm,
+        }
+    }
 }
 
 fn main() {
     let args = Args::parse();
 
-    let mut monkey = Monkey::default();
+    let mut monkey = Monkey::new(args.backend.into());
+
+    if let Some(seed) = args.seed {
+        monkey.seed(seed);
+    }
 
 
     if let Some(file) = args.file {
-        let contents = fs::read_to_string(file).expect("Unable to find or read file");
+        let contents = fs::read_to_string(&file).expect("Unable to find or read file");
 
-        if let Ok(program) = lex_and_parse(contents) {
-            evaluate(program, &mut monkey);
+        if let Some(dir) = Path::new(&file).parent() {
+            monkey.set_import_dir(dir);
+        }
+
+        if let Ok(program) = lex_and_parse(&contents, &file) {
+            evaluate(program, &mut monkey, &contents, &file);
         }
     } else {
         println!("Monkey v1.0");
@@ -38,8 +68,8 @@ fn main() {
         loop {
             let input = get_input();
 
-            if let Ok(program) = lex_and_parse(input) {
-                evaluate(program, &mut monkey);
+            if let Ok(program) = lex_and_parse(&input, REPL_FILE_NAME) {
+                evaluate(program, &mut monkey, &input, REPL_FILE_NAME);
             }
         }
     }
@@ -56,31 +86,19 @@ fn get_input() -> String {
     input
 }
 
-fn evaluate(program: Program, monkey: &mut Monkey) {
-    if let Err(msg) = monkey.eval(program) {
-        println!("Error: {msg}");
+fn evaluate(program: Program, monkey: &mut Monkey, source: &str, file_name: &str) {
+    if let Err(err) = monkey.eval(program) {
+        print_error(&err, source, file_name);
     }
 }
 
-fn lex_and_parse(input: String) -> Result<Program, ()> {
-    let lexer = Lexer::new(input.as_str());
-
-    let mut parser = parser::Parser::new(lexer);
-
-    let program = parser.parse_program();
-
-    if !parser.errors.is_empty() {
-        print_parse_errors(&parser.errors);
-        return Err(());
-    }
-
-    Ok(program)
+fn lex_and_parse(source: &str, file_name: &str) -> Result<Program, ()> {
+    Monkey::parse(source).map_err(|err| print_error(&err, source, file_name))
 }
 
-fn print_parse_errors(errors: &Vec<String>) {
-    println!("Woops! We ran into some monkey business here!");
-    println!(" parser errors: ");
-    for err in errors {
-        println!("\t{err}");
-    }
+fn print_error(err: &Error, source: &str, file_name: &str) {
+    // Only color the output when it is going to a terminal and the user has not opted out
+    let color = stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+
+    print!("{}", err.render(source, file_name, color));
 }